@@ -0,0 +1,56 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+
+[[group(2), binding(0)]]
+var<uniform> mesh: Mesh;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] layer: u32;
+    [[location(4)]] color: vec4<f32>;
+    [[location(5)]] animation: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1), interpolate(flat)]] layer: u32;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct ChunkTime {
+    time: f32;
+};
+[[group(1), binding(2)]]
+var<uniform> chunk_time: ChunkTime;
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+    out.uv = vertex.uv;
+    // animation frames are the layers after the first one, each shown for a while
+    let frame = u32(chunk_time.time / vertex.animation.y) % u32(vertex.animation.x);
+    out.layer = vertex.layer + frame;
+    out.color = vertex.color;
+    return out;
+}
+
+[[group(1), binding(0)]]
+var textures: texture_2d_array<f32>;
+[[group(1), binding(1)]]
+var textures_sampler: sampler;
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // uv counts blocks across the quad, the sampler repeats the texture once per block
+    let color = textureSample(textures, textures_sampler, in.uv, i32(in.layer)) * in.color;
+#ifdef ALPHA_MASK
+    if (color.a < 0.5) {
+        discard;
+    }
+#endif
+    return color;
+}
@@ -0,0 +1,48 @@
+//! Prints how many bytes the blocks of the test scene main.rs used to build take up,
+//! stored as a palette and as the `Box<[Option<Block>; CHUNK_VOLUME]>` chunks used to be.
+//!
+//! cargo run --release --example chunk_memory
+use std::mem::size_of;
+
+use bevy_craft_new::block::Block;
+use bevy_craft_new::chunk::{Chunk, ChunkGrid};
+use bevy_craft_new::coords::{ChunkPos, LocalPos, WorldPos, CHUNK_VOLUME};
+
+fn main() {
+    // seven chunks full of logs and one of air
+    let mut grid = ChunkGrid::new();
+    let log = grid.registry.by_name("log").expect("log is a built-in block");
+    for a in 0..2 {
+        for c in 0..2 {
+            for b in 0..2 {
+                let mut chunk = Chunk::new(ChunkPos::new(a, b, c));
+                if !(a == 0 && b == 1 && c == 0) {
+                    for pos in LocalPos::all() {
+                        chunk.set_block(log, pos);
+                    }
+                }
+                grid.set_chunk(chunk);
+            }
+        }
+    }
+    report("test scene", grid.chunks.values().map(|c| &**c));
+
+    // a second kind of block makes the chunk store an index per block
+    let stone = grid.registry.by_name("stone").expect("stone is a built-in block");
+    grid.set_block(WorldPos::new(3, 3, 3), stone);
+    let chunk = grid.get_chunk_from_coords(ChunkPos::new(0, 0, 0)).unwrap();
+    report("one stone in a chunk of logs", std::iter::once(chunk));
+}
+
+fn report<'a>(scene: &str, chunks: impl Iterator<Item = &'a Chunk>) {
+    let (mut count, mut packed) = (0, 0);
+    for chunk in chunks {
+        count += 1;
+        packed += chunk.blocks.heap_size();
+    }
+    let unpacked = count * CHUNK_VOLUME * size_of::<Option<Block>>();
+    println!(
+        "{} ({} chunks): {} bytes before, {} bytes paletted",
+        scene, count, unpacked, packed
+    );
+}
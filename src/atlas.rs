@@ -0,0 +1,444 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use bevy::utils::HashMap;
+
+use crate::material::{BlockTextures, ChunkMaterial, ChunkMaterials};
+
+/// Layer of a block texture in the [`BlockTextures`] array, see [`AtlasLayout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Tile(pub u16);
+
+impl Tile {
+    /// Shown for textures the atlas doesn't have.
+    pub const MISSING: Tile = Tile(0);
+}
+
+/// Which layer each block texture ended up in. Layers are squares of the same size, the
+/// first one is the missing texture. Animated textures take one layer per frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasLayout {
+    /// Pixels along the edge of a layer.
+    pub tile_size: u32,
+    tiles: HashMap<String, Tile>,
+    /// Frame count of the animated textures, by their first layer.
+    frames: HashMap<Tile, u16>,
+    layers: usize,
+}
+
+impl AtlasLayout {
+    /// Gives each name a layer after the missing texture.
+    pub fn new(tile_size: u32, names: impl IntoIterator<Item = String>) -> Self {
+        Self::with_frames(tile_size, names.into_iter().map(|name| (name, 1)))
+    }
+    /// Gives each name as many layers as it has frames, after the missing texture.
+    pub fn with_frames(tile_size: u32, textures: impl IntoIterator<Item = (String, u16)>) -> Self {
+        let mut layout = Self {
+            tile_size,
+            tiles: HashMap::default(),
+            frames: HashMap::default(),
+            layers: 1,
+        };
+        for (name, frames) in textures {
+            if layout.tiles.contains_key(&name) {
+                continue;
+            }
+            let tile = Tile(layout.layers as u16);
+            layout.tiles.insert(name, tile);
+            if frames > 1 {
+                layout.frames.insert(tile, frames);
+            }
+            layout.layers += frames as usize;
+        }
+        layout
+    }
+    pub fn get(&self, name: &str) -> Option<Tile> {
+        self.tiles.get(name).copied()
+    }
+    /// How many frames the texture starting at `tile` has, 1 unless it's animated.
+    pub fn frames(&self, tile: Tile) -> u16 {
+        self.frames.get(&tile).copied().unwrap_or(1)
+    }
+    /// Number of textures, including the missing texture.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len() + 1
+    }
+    /// Number of layers, one per frame of each texture.
+    pub fn layer_count(&self) -> usize {
+        self.layers
+    }
+}
+
+impl Default for AtlasLayout {
+    fn default() -> Self {
+        Self::new(16, [])
+    }
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// The image isn't in a format that converts to 8 bit RGBA.
+    UnsupportedFormat(String),
+    /// Tiles have to be square, or a vertical strip of square animation frames.
+    NotSquare(String),
+    /// The image has no pixels.
+    Empty(String),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::UnsupportedFormat(name) => {
+                write!(f, "block texture {} has an unsupported format", name)
+            }
+            AtlasError::NotSquare(name) => write!(
+                f,
+                "block texture {} isn't square or a strip of square frames",
+                name
+            ),
+            AtlasError::Empty(name) => write!(f, "block texture {} is empty", name),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+/// Packs block textures into the layers of [`BlockTextures`]. Textures may have any
+/// resolution, they're scaled up to the largest one without blurring. Textures taller than
+/// they're wide are animations, with the frames stacked from top to bottom. Adding a
+/// texture under a name that's already there replaces it, which is how resource packs
+/// override built-in textures.
+#[derive(Debug, Clone, Default)]
+pub struct AtlasBuilder {
+    textures: HashMap<String, Image>,
+}
+
+impl AtlasBuilder {
+    pub fn add(&mut self, name: impl Into<String>, image: &Image) -> Result<(), AtlasError> {
+        let name = name.into();
+        let size = image.texture_descriptor.size;
+        if size.width == 0 {
+            return Err(AtlasError::Empty(name));
+        }
+        if !size.height.is_multiple_of(size.width) {
+            return Err(AtlasError::NotSquare(name));
+        }
+        let image = match image.texture_descriptor.format {
+            TextureFormat::Rgba8UnormSrgb => image.clone(),
+            _ => image
+                .convert(TextureFormat::Rgba8UnormSrgb)
+                .ok_or_else(|| AtlasError::UnsupportedFormat(name.clone()))?,
+        };
+        self.textures.insert(name, image);
+        Ok(())
+    }
+    pub fn build(&self) -> (BlockTextures, AtlasLayout) {
+        let tile_size = self
+            .textures
+            .values()
+            .map(|image| image.texture_descriptor.size.width)
+            .max()
+            .unwrap_or(16);
+        // sorted so the same textures always get the same layers
+        let mut names: Vec<&String> = self.textures.keys().collect();
+        names.sort();
+        let frames = |image: &Image| {
+            let size = image.texture_descriptor.size;
+            (size.height / size.width) as u16
+        };
+        let layout = AtlasLayout::with_frames(
+            tile_size,
+            names
+                .iter()
+                .map(|name| (name.to_string(), frames(&self.textures[*name]))),
+        );
+
+        let layer_len = (tile_size * tile_size * 4) as usize;
+        let mut data = vec![0; layer_len * layout.layer_count()];
+        let mut blit = |layer: usize, pixel: &dyn Fn(u32, u32) -> [u8; 4]| {
+            let layer = &mut data[layer * layer_len..][..layer_len];
+            for y in 0..tile_size {
+                for x in 0..tile_size {
+                    let i = ((y * tile_size + x) * 4) as usize;
+                    layer[i..i + 4].copy_from_slice(&pixel(x, y));
+                }
+            }
+        };
+        // magenta and black checkers
+        blit(Tile::MISSING.0 as usize, &|x, y| {
+            if (x * 2 / tile_size + y * 2 / tile_size) % 2 == 0 {
+                [255, 0, 255, 255]
+            } else {
+                [0, 0, 0, 255]
+            }
+        });
+        for name in names {
+            let image = &self.textures[name];
+            let width = image.texture_descriptor.size.width;
+            let first = layout.get(name).unwrap().0 as u32;
+            for frame in 0..frames(image) as u32 {
+                blit((first + frame) as usize, &|x, y| {
+                    // nearest neighbour
+                    let (x, y) = (x * width / tile_size, y * width / tile_size + frame * width);
+                    let i = ((y * width + x) * 4) as usize;
+                    image.data[i..i + 4].try_into().unwrap()
+                });
+            }
+        }
+
+        (BlockTextures::new(tile_size, data), layout)
+    }
+}
+
+/// Resource packs whose `blocks/` textures replace the built-in ones, by folder name under
+/// `assets/resourcepacks/`. Later packs win.
+#[derive(Debug, Clone, Default)]
+pub struct ResourcePacks(pub Vec<String>);
+
+/// The block texture array and which layer each texture is in. Only has the missing
+/// texture until every block texture has loaded.
+pub struct BlockAtlas {
+    pub textures: Handle<BlockTextures>,
+    pub layout: Arc<AtlasLayout>,
+}
+
+/// The block textures per folder, in the order they override each other.
+struct BlockTextureFolders {
+    handles: Vec<Vec<HandleUntyped>>,
+    /// Whether the atlas needs building once they've all loaded.
+    changed: bool,
+}
+
+/// Builds the [`BlockAtlas`] from the textures in `assets/blocks/` and the
+/// [`ResourcePacks`]. With the asset server watching for changes, editing a texture
+/// rebuilds the atlas and redraws every chunk with it.
+pub struct BlockAtlasPlugin;
+
+impl Plugin for BlockAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ResourcePacks>()
+            .add_startup_system(load_block_textures)
+            .add_system(watch_block_textures.label(AtlasSystem::Watch))
+            .add_system(build_block_atlas.after(AtlasSystem::Watch))
+            .add_system(update_materials);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+enum AtlasSystem {
+    Watch,
+}
+
+fn load_block_textures(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    packs: Res<ResourcePacks>,
+    mut block_textures: ResMut<Assets<BlockTextures>>,
+) {
+    let folders = std::iter::once("blocks".to_string()).chain(
+        packs
+            .0
+            .iter()
+            .map(|pack| format!("resourcepacks/{}/blocks", pack)),
+    );
+    let handles = folders
+        .filter_map(|folder| match asset_server.load_folder(&folder) {
+            Ok(handles) => Some(handles),
+            Err(e) => {
+                warn!("Can't load block textures from {}: {:?}", folder, e);
+                None
+            }
+        })
+        .collect();
+    commands.insert_resource(BlockTextureFolders {
+        handles,
+        changed: true,
+    });
+
+    let (textures, layout) = AtlasBuilder::default().build();
+    commands.insert_resource(BlockAtlas {
+        textures: block_textures.add(textures),
+        layout: Arc::new(layout),
+    });
+}
+
+/// Marks the atlas for rebuilding when one of its textures is edited.
+fn watch_block_textures(
+    mut events: EventReader<AssetEvent<Image>>,
+    folders: Option<ResMut<BlockTextureFolders>>,
+) {
+    let mut folders = match folders {
+        Some(folders) => folders,
+        None => return,
+    };
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if folders.handles.iter().flatten().any(|h| h.id == handle.id) {
+                folders.changed = true;
+            }
+        }
+    }
+}
+
+/// Packs the block textures once they're all loaded, or failed to.
+fn build_block_atlas(
+    folders: Option<ResMut<BlockTextureFolders>>,
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    mut block_textures: ResMut<Assets<BlockTextures>>,
+    mut atlas: ResMut<BlockAtlas>,
+) {
+    let mut folders = match folders {
+        Some(folders) if folders.changed => folders,
+        _ => return,
+    };
+    let ids = folders.handles.iter().flatten().map(|handle| handle.id);
+    if !matches!(
+        asset_server.get_group_load_state(ids),
+        LoadState::Loaded | LoadState::Failed
+    ) {
+        return;
+    }
+
+    let mut builder = AtlasBuilder::default();
+    for handle in folders.handles.iter().flatten() {
+        let name = asset_server.get_handle_path(handle).and_then(|path| {
+            let stem = path.path().file_stem()?;
+            Some(stem.to_string_lossy().into_owned())
+        });
+        if let (Some(name), Some(image)) = (name, images.get(handle)) {
+            if let Err(e) = builder.add(name, image) {
+                warn!("{}", e);
+            }
+        }
+    }
+    let (textures, layout) = builder.build();
+    info!(
+        "Packed {} block textures of {}px",
+        layout.tile_count() - 1,
+        layout.tile_size
+    );
+    // a new asset rather than replacing the old one, so materials wait until it's on the GPU
+    atlas.textures = block_textures.add(textures);
+    atlas.layout = Arc::new(layout);
+    folders.changed = false;
+}
+
+/// Points the chunk materials at the current block textures.
+fn update_materials(
+    atlas: Res<BlockAtlas>,
+    chunk_materials: Res<ChunkMaterials>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    if atlas.is_changed() {
+        chunk_materials.set_textures(&atlas.textures, &mut materials);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    fn solid(size: u32, color: [u8; 4]) -> Image {
+        rect(size, size, color)
+    }
+
+    fn rect(width: u32, height: u32, color: [u8; 4]) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &color,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn packs_tiles_of_any_size() {
+        let mut builder = AtlasBuilder::default();
+        builder
+            .add("stone", &solid(16, [128, 128, 128, 255]))
+            .unwrap();
+        builder.add("dirt", &solid(32, [100, 60, 20, 255])).unwrap();
+        builder.add("grass", &solid(8, [0, 255, 0, 255])).unwrap();
+        // a resource pack replaces the grass
+        builder.add("grass", &solid(4, [0, 200, 0, 255])).unwrap();
+        assert!(matches!(
+            builder.add("wide", &rect(32, 16, [0; 4])),
+            Err(AtlasError::NotSquare(_))
+        ));
+        let empty = Image::new(
+            Extent3d {
+                width: 0,
+                height: 16,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        assert!(matches!(
+            builder.add("empty", &empty),
+            Err(AtlasError::Empty(_))
+        ));
+
+        let (textures, layout) = builder.build();
+        assert_eq!(32, layout.tile_size);
+        assert_eq!(4, layout.tile_count());
+        assert_eq!(4, textures.layers);
+        // 32, 16, 8, 4, 2 and 1 pixels
+        assert_eq!(6, textures.mips.len());
+        assert_eq!(4 * 4 * 4 * 4, textures.mips[3].len());
+
+        let grass = layout.get("grass").unwrap().0 as u32;
+        assert_eq!([0, 200, 0, 255], textures.pixel(0, grass, 31, 31));
+        let stone = layout.get("stone").unwrap().0 as u32;
+        assert_eq!([128, 128, 128, 255], textures.pixel(0, stone, 5, 30));
+        assert_eq!([255, 0, 255, 255], textures.pixel(0, 0, 0, 0));
+        assert_eq!(None, layout.get("wide"));
+        // solid textures stay the same all the way down, the checkers average out
+        assert_eq!([128, 128, 128, 255], textures.pixel(5, stone, 0, 0));
+        assert_eq!([128, 0, 128, 255], textures.pixel(5, 0, 0, 0));
+    }
+
+    #[test]
+    fn splits_animation_strips() {
+        let mut builder = AtlasBuilder::default();
+        builder
+            .add("stone", &solid(4, [128, 128, 128, 255]))
+            .unwrap();
+        // three frames, each a shade lighter
+        let mut strip = rect(4, 12, [0, 0, 0, 255]);
+        for (i, pixel) in strip.data.chunks_mut(4).enumerate() {
+            pixel[0] = (i / 16 * 100) as u8;
+        }
+        builder.add("lava", &strip).unwrap();
+
+        let (textures, layout) = builder.build();
+        let lava = layout.get("lava").unwrap();
+        let stone = layout.get("stone").unwrap();
+        assert_eq!(3, layout.frames(lava));
+        assert_eq!(1, layout.frames(stone));
+        assert_eq!(3, layout.tile_count());
+        assert_eq!(5, layout.layer_count());
+        assert_eq!(5, textures.layers);
+        for frame in 0..3 {
+            let layer = lava.0 as u32 + frame;
+            assert_eq!(
+                [frame as u8 * 100, 0, 0, 255],
+                textures.pixel(0, layer, 3, 3)
+            );
+        }
+        assert_eq!(
+            [128, 128, 128, 255],
+            textures.pixel(0, stone.0 as u32, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use serde::Deserialize;
+
+use crate::noise::Noise;
+
+/// Which color map a tinted block face is colored with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Tint {
+    Grass,
+    Foliage,
+}
+
+/// The kind of land a column is in, picked from the climate there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Plains,
+    Forest,
+    Desert,
+    Tundra,
+    Mountains,
+}
+
+/// How high a biome's terrain lies and how rough it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainProfile {
+    /// Average height of the surface.
+    pub base_height: f64,
+    /// How far hills rise above and valleys sink below the base height, at most.
+    pub amplitude: f64,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 6] = [
+        Biome::Ocean,
+        Biome::Plains,
+        Biome::Forest,
+        Biome::Desert,
+        Biome::Tundra,
+        Biome::Mountains,
+    ];
+
+    /// Name of the block the columns of this biome are topped with.
+    pub fn surface(self) -> &'static str {
+        match self {
+            Biome::Ocean | Biome::Desert => "sand",
+            Biome::Plains | Biome::Forest => "grass",
+            Biome::Tundra => "snow",
+            Biome::Mountains => "stone",
+        }
+    }
+    /// Name of the block between the surface and the stone.
+    pub fn subsurface(self) -> &'static str {
+        match self {
+            Biome::Ocean | Biome::Desert => "sand",
+            Biome::Plains | Biome::Forest | Biome::Tundra => "dirt",
+            Biome::Mountains => "stone",
+        }
+    }
+    pub fn terrain(self) -> TerrainProfile {
+        let (base_height, amplitude) = match self {
+            Biome::Ocean => (12., 8.),
+            Biome::Plains => (33., 6.),
+            Biome::Forest => (35., 12.),
+            Biome::Desert => (32., 8.),
+            Biome::Tundra => (36., 14.),
+            Biome::Mountains => (54., 40.),
+        };
+        TerrainProfile {
+            base_height,
+            amplitude,
+        }
+    }
+    /// The color tinted faces of this biome are multiplied with.
+    pub fn color(self, tint: Tint) -> [f32; 3] {
+        match (tint, self) {
+            (Tint::Grass, Biome::Ocean) => [0.36, 0.74, 0.40],
+            (Tint::Grass, Biome::Plains) => [0.50, 0.74, 0.30],
+            (Tint::Grass, Biome::Forest) => [0.28, 0.80, 0.20],
+            (Tint::Grass, Biome::Desert) => [0.70, 0.72, 0.33],
+            (Tint::Grass, Biome::Tundra) => [0.50, 0.71, 0.59],
+            (Tint::Grass, Biome::Mountains) => [0.45, 0.65, 0.45],
+            // leaves are a bit darker and more saturated than grass in the same place
+            (Tint::Foliage, Biome::Ocean) => [0.24, 0.68, 0.26],
+            (Tint::Foliage, Biome::Plains) => [0.40, 0.68, 0.14],
+            (Tint::Foliage, Biome::Forest) => [0.10, 0.73, 0.04],
+            (Tint::Foliage, Biome::Desert) => [0.62, 0.64, 0.16],
+            (Tint::Foliage, Biome::Tundra) => [0.38, 0.63, 0.52],
+            (Tint::Foliage, Biome::Mountains) => [0.34, 0.57, 0.34],
+        }
+    }
+    /// How far the climate at a column is from this biome's ideal one. Oceans and mountains
+    /// only care about continentalness, the other biomes sit inland at some temperature and
+    /// humidity.
+    fn distance(self, temperature: f64, humidity: f64, continentalness: f64) -> f64 {
+        let (t, h) = match self {
+            Biome::Ocean => return (continentalness - 0.1).abs(),
+            Biome::Mountains => return (continentalness - 0.9).abs(),
+            Biome::Plains => (0.55, 0.4),
+            Biome::Forest => (0.55, 0.8),
+            Biome::Desert => (0.85, 0.1),
+            Biome::Tundra => (0.15, 0.5),
+        };
+        let c = continentalness - 0.55;
+        ((temperature - t).powi(2) + (humidity - h).powi(2) + c * c).sqrt()
+    }
+}
+
+/// How much each biome counts at a column, so terrain and colors fade from one biome into
+/// the next instead of changing at a hard edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeBlend {
+    /// Indexed like [`Biome::ALL`], summing up to 1.
+    weights: [f64; Biome::ALL.len()],
+}
+
+impl BiomeBlend {
+    /// Climate distance over which a biome fades out, the width of the borders.
+    const BLEND: f64 = 0.04;
+
+    fn new(temperature: f64, humidity: f64, continentalness: f64) -> Self {
+        let distances = Biome::ALL.map(|b| b.distance(temperature, humidity, continentalness));
+        let nearest = distances.iter().cloned().fold(f64::INFINITY, f64::min);
+        // relative to the nearest biome so the weights can't all round to zero
+        let mut weights = distances.map(|d| (-(d - nearest) / Self::BLEND).exp());
+        let total: f64 = weights.iter().sum();
+        for w in &mut weights {
+            *w /= total;
+        }
+        Self { weights }
+    }
+    pub fn weight(&self, biome: Biome) -> f64 {
+        self.weights[biome as usize]
+    }
+    /// The biome that counts the most.
+    pub fn dominant(&self) -> Biome {
+        let mut dominant = Biome::ALL[0];
+        for biome in Biome::ALL {
+            if self.weight(biome) > self.weight(dominant) {
+                dominant = biome;
+            }
+        }
+        dominant
+    }
+    /// The biomes' terrain profiles, weighted.
+    pub fn terrain(&self) -> TerrainProfile {
+        let mut terrain = TerrainProfile {
+            base_height: 0.,
+            amplitude: 0.,
+        };
+        for biome in Biome::ALL {
+            let (w, t) = (self.weight(biome), biome.terrain());
+            terrain.base_height += t.base_height * w;
+            terrain.amplitude += t.amplitude * w;
+        }
+        terrain
+    }
+    /// The biomes' tint colors, weighted.
+    pub fn color(&self, tint: Tint) -> [f32; 3] {
+        let mut color = [0.; 3];
+        for biome in Biome::ALL {
+            let (w, c) = (self.weight(biome) as f32, biome.color(tint));
+            for i in 0..3 {
+                color[i] += c[i] * w;
+            }
+        }
+        color
+    }
+}
+
+/// Temperature, humidity and continentalness across the world, smoothly varying with x and
+/// z, and the biomes they make up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Climate {
+    temperature: Noise,
+    humidity: Noise,
+    continentalness: Noise,
+}
+
+impl Climate {
+    /// Blocks across one climate feature, roughly.
+    const SCALE: f64 = 512.;
+    /// Continents and oceans are larger still.
+    const CONTINENT_SCALE: f64 = 1024.;
+
+    pub fn new(seed: u32) -> Self {
+        Self {
+            temperature: Noise::new(seed),
+            humidity: Noise::new(seed.wrapping_add(0x5bd1_e995)),
+            continentalness: Noise::new(seed.wrapping_add(0x27d4_eb2f)),
+        }
+    }
+    /// From 0 (cold) to 1 (hot).
+    pub fn temperature(&self, x: f64, z: f64) -> f64 {
+        let t = self.temperature.fbm2(x / Self::SCALE, z / Self::SCALE, 3);
+        (t * 0.75 + 0.5).clamp(0., 1.)
+    }
+    /// From 0 (dry) to 1 (wet).
+    pub fn humidity(&self, x: f64, z: f64) -> f64 {
+        let h = self.humidity.fbm2(x / Self::SCALE, z / Self::SCALE, 3);
+        (h * 0.75 + 0.5).clamp(0., 1.)
+    }
+    /// From 0 (open sea) to 1 (mountain ranges), with most land in between.
+    pub fn continentalness(&self, x: f64, z: f64) -> f64 {
+        let (x, z) = (x / Self::CONTINENT_SCALE, z / Self::CONTINENT_SCALE);
+        (self.continentalness.fbm2(x, z, 3) * 0.9 + 0.55).clamp(0., 1.)
+    }
+    /// How much each biome counts at `x`, `z`.
+    pub fn blend(&self, x: f64, z: f64) -> BiomeBlend {
+        BiomeBlend::new(
+            self.temperature(x, z),
+            self.humidity(x, z),
+            self.continentalness(x, z),
+        )
+    }
+    /// The biome the column at `x`, `z` belongs to.
+    pub fn biome_at(&self, x: f64, z: f64) -> Biome {
+        self.blend(x, z).dominant()
+    }
+    /// The color a tinted face at `x`, `z` is multiplied with.
+    pub fn color(&self, tint: Tint, x: f64, z: f64) -> [f32; 3] {
+        self.blend(x, z).color(tint)
+    }
+}
+
+impl Default for Climate {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grass_color_varies() {
+        let climate = Climate::default();
+        let colors: Vec<_> = (0..32)
+            .map(|i| climate.color(Tint::Grass, i as f64 * 300., i as f64 * -170.))
+            .collect();
+        assert!(colors.iter().any(|c| *c != colors[0]));
+        for c in colors {
+            // always some shade of green
+            assert!(c[1] >= c[0] && c[1] >= c[2]);
+        }
+        // nearby blocks look almost the same
+        let a = climate.color(Tint::Grass, 100., 100.);
+        let b = climate.color(Tint::Grass, 101., 100.);
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 0.01));
+    }
+
+    #[test]
+    fn biomes_blend_at_borders() {
+        let climate = Climate::new(3);
+        let mut seen = vec![];
+        let mut previous = climate.blend(0., 0.);
+        for i in 1..16000 {
+            let (x, z) = (i as f64, i as f64 * -0.5);
+            let blend = climate.blend(x, z);
+            let biome = climate.biome_at(x, z);
+            if !seen.contains(&biome) {
+                seen.push(biome);
+            }
+            let total: f64 = Biome::ALL.iter().map(|b| blend.weight(*b)).sum();
+            assert!((total - 1.).abs() < 1e-9);
+            // a step never changes the terrain by a whole block, even where the biome does
+            let (a, b) = (previous.terrain(), blend.terrain());
+            assert!((a.base_height - b.base_height).abs() < 1.);
+            assert!((a.amplitude - b.amplitude).abs() < 1.);
+            previous = blend;
+        }
+        assert!(seen.len() >= 4, "only saw {:?}", seen);
+    }
+}
@@ -1,125 +1,556 @@
-#[derive(Debug, PartialEq, Clone)]
-pub struct Block(pub u8);
-
-pub enum Texture {
-    Grass,
-    Stone,
-    Dirt,
-    Plank,
-    Slab,
-    Brick,
-    Tnt,
-    Cobweb,
-    Cobblestone,
-    Log,
-    Missing,
+use std::fmt;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+
+use crate::atlas::{AtlasLayout, Tile};
+use crate::biome::Tint;
+use crate::coords::Face;
+use crate::shape::{BoxModel, Shape};
+use crate::state::{BlockState, Placement};
+
+/// A block id and its state. What the id means is up to the [`BlockRegistry`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Block {
+    pub id: u8,
+    pub state: BlockState,
 }
 
 impl Block {
     pub(crate) const EMPTY: Option<Block> = None;
-    pub fn new(texture: Texture) -> Self {
-        // 64 textures | 6 bits
-        match texture {
-            Texture::Grass => Self(0),
-            Texture::Stone => Self(1),
-            Texture::Dirt => Self(2),
-            Texture::Plank => Self(4),
-            Texture::Slab => Self(5),
-            Texture::Brick => Self(7),
-            Texture::Tnt => Self(8),
-            Texture::Cobweb => Self(11),
-            Texture::Cobblestone => Self(16),
-            Texture::Log => Self(20),
-            Texture::Missing => Self(254),
+
+    /// A block in its default state.
+    pub const fn new(id: u8) -> Self {
+        Self {
+            id,
+            state: BlockState::from_bits(0),
         }
     }
-    pub fn get_texture(&self) -> Texture {
-        match self.0 {
-            0 => Texture::Grass,
-            1 => Texture::Stone,
-            2 => Texture::Dirt,
-            4 => Texture::Plank,
-            5 => Texture::Slab,
-            7 => Texture::Brick,
-            8 => Texture::Tnt,
-            11 => Texture::Cobweb,
-            16 => Texture::Cobblestone,
-            20 => Texture::Log,
-            _ => Texture::Missing,
+    pub fn with_state(self, state: BlockState) -> Self {
+        Self { state, ..self }
+    }
+}
+
+/// How a block is drawn, which decides the mesh its faces end up in and what it hides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum RenderClass {
+    /// Fully covers its faces and hides whatever is behind them.
+    #[default]
+    Opaque,
+    /// Pixels are either fully visible or cut out, like leaves and cobwebs.
+    Cutout,
+    /// Blended with what's behind it, like stained glass.
+    Translucent,
+    /// Blended like translucent blocks, but kept apart so water can be drawn differently.
+    Liquid,
+}
+
+impl RenderClass {
+    pub const ALL: [RenderClass; 4] = [
+        RenderClass::Opaque,
+        RenderClass::Cutout,
+        RenderClass::Translucent,
+        RenderClass::Liquid,
+    ];
+
+    /// Position of this class in [`RenderClass::ALL`] and in per-class arrays.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Per-face values as written in the registry. The most specific entry wins: a single face,
+/// then `top`, `bottom` or `side`, then `all`. Top and bottom are +y and -y.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Faces<T> {
+    all: Option<T>,
+    side: Option<T>,
+    top: Option<T>,
+    bottom: Option<T>,
+    pos_x: Option<T>,
+    neg_x: Option<T>,
+    pos_z: Option<T>,
+    neg_z: Option<T>,
+}
+
+impl<T: Clone> Faces<T> {
+    pub fn get(&self, face: Face) -> Option<T> {
+        let (single, group) = match face {
+            Face::PosY => (&None, &self.top),
+            Face::NegY => (&None, &self.bottom),
+            Face::PosX => (&self.pos_x, &self.side),
+            Face::NegX => (&self.neg_x, &self.side),
+            Face::PosZ => (&self.pos_z, &self.side),
+            Face::NegZ => (&self.neg_z, &self.side),
+        };
+        single
+            .as_ref()
+            .or(group.as_ref())
+            .or(self.all.as_ref())
+            .cloned()
+    }
+}
+
+impl<T> Default for Faces<T> {
+    fn default() -> Self {
+        Self {
+            all: None,
+            side: None,
+            top: None,
+            bottom: None,
+            pos_x: None,
+            neg_x: None,
+            pos_z: None,
+            neg_z: None,
         }
     }
-    pub fn get_texture_uv(&self) -> [[f32; 2]; 24] {
-        let uv = match self.0 {
-            0 => [
-                [[3., 4.], [0., 1.]],
-                [[3., 4.], [0., 1.]],
-                [[3., 4.], [0., 1.]],
-                [[3., 4.], [0., 1.]],
-                [[0., 1.], [0., 1.]],
-                [[2., 3.], [0., 1.]],
-            ],
-            5 => [
-                [[5., 6.], [0., 1.]],
-                [[5., 6.], [0., 1.]],
-                [[5., 6.], [0., 1.]],
-                [[5., 6.], [0., 1.]],
-                [[6., 7.], [0., 1.]],
-                [[6., 7.], [0., 1.]],
-            ],
-            8 => [
-                [[9., 8.], [0., 1.]],
-                [[9., 8.], [0., 1.]],
-                [[8., 9.], [0., 1.]],
-                [[8., 9.], [0., 1.]],
-                [[9., 10.], [0., 1.]],
-                [[10., 11.], [0., 1.]],
-            ],
-            20 => [
-                [[4., 5.], [1., 2.]],
-                [[4., 5.], [1., 2.]],
-                [[4., 5.], [1., 2.]],
-                [[4., 5.], [1., 2.]],
-                [[5., 6.], [1., 2.]],
-                [[5., 6.], [1., 2.]],
-            ],
-            _ => {
-                [[
-                    [self.0 as f32, (self.0 + 1) as f32],
-                    [(self.0 / 16) as f32, ((self.0 / 16) + 1) as f32],
-                ]; 6]
+}
+
+/// One block as written in the registry file.
+#[derive(Debug, Clone, Deserialize)]
+struct BlockDesc {
+    id: u8,
+    name: String,
+    textures: Faces<String>,
+    #[serde(default)]
+    tint: Faces<Tint>,
+    #[serde(default = "default_solid")]
+    solid: bool,
+    #[serde(default)]
+    render: RenderClass,
+    #[serde(default)]
+    shape: Shape,
+    #[serde(default)]
+    placement: Placement,
+    #[serde(default = "default_hardness")]
+    hardness: f32,
+    #[serde(default)]
+    frame_time: Option<f32>,
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+fn default_hardness() -> f32 {
+    1.
+}
+
+/// Everything the game knows about a kind of block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockDef {
+    pub id: u8,
+    pub name: String,
+    /// Texture of each face in [`Face::ALL`] order, by file name in `assets/blocks/`
+    /// without the extension.
+    pub textures: [String; 6],
+    /// Where each texture is in the atlas, see [`BlockRegistry::resolve_tiles`].
+    pub tiles: [Tile; 6],
+    /// How many frames each texture has, textures taller than wide are strips of frames.
+    pub frames: [u16; 6],
+    /// Seconds each animation frame shows. Without it only the first frame is drawn.
+    pub frame_time: Option<f32>,
+    /// Faces colored by the climate they're in. The atlas keeps these grey.
+    pub tint: [Option<Tint>; 6],
+    /// Whether entities collide with the block.
+    pub solid: bool,
+    pub render: RenderClass,
+    pub shape: Shape,
+    pub placement: Placement,
+    /// How long the block takes to break.
+    pub hardness: f32,
+}
+
+impl BlockDef {
+    pub fn texture(&self, face: Face) -> &str {
+        &self.textures[face.index()]
+    }
+    pub fn tile(&self, face: Face) -> Tile {
+        self.tiles[face.index()]
+    }
+    pub fn tint(&self, face: Face) -> Option<Tint> {
+        self.tint[face.index()]
+    }
+    /// Frame count and seconds per frame of the texture on `face`, as the chunk shader
+    /// animates it. Still textures have a single frame.
+    pub fn animation(&self, face: Face) -> [f32; 2] {
+        match self.frame_time {
+            Some(frame_time) => [self.frames[face.index()] as f32, frame_time],
+            None => [1., 1.],
+        }
+    }
+    /// What `face` looks like with the block in `state`, turned along its axis.
+    pub fn face(&self, face: Face, state: BlockState) -> FaceTexture {
+        let (local, rotated) = state.unrotate(face);
+        FaceTexture {
+            tile: self.tile(local),
+            tint: self.tint(local),
+            animation: self.animation(local),
+            rotated,
+        }
+    }
+    /// Whether the block hides the faces of its neighbours and darkens their corners.
+    pub fn occludes(&self) -> bool {
+        self.render == RenderClass::Opaque && self.shape == Shape::Cube
+    }
+    /// Whether the block's shape fills `face` completely in `state`, see [`Shape::covers`].
+    pub fn covers(&self, face: Face, state: BlockState) -> bool {
+        self.shape.covers(face, state)
+    }
+    /// Whether this block hides the face of `neighbour` that touches it. Besides opaque
+    /// blocks, a pane of glass or a body of water hides the faces between its own blocks,
+    /// while cutout blocks like leaves keep them so you can see into the foliage.
+    pub fn hides(&self, neighbour: &BlockDef) -> bool {
+        match self.render {
+            RenderClass::Opaque => true,
+            RenderClass::Cutout => false,
+            RenderClass::Translucent | RenderClass::Liquid => self.id == neighbour.id,
+        }
+    }
+    /// Whether this block in `state` hides the face of `neighbour` looking at it through
+    /// `face`: the side of this block touching it has to be full, see [`BlockDef::covers`]
+    /// and [`BlockDef::hides`].
+    pub fn hides_face(&self, state: BlockState, face: Face, neighbour: &BlockDef) -> bool {
+        self.covers(face.opposite(), state) && self.hides(neighbour)
+    }
+    /// Stand-in for ids the registry doesn't know.
+    fn missing() -> Self {
+        Self {
+            id: u8::MAX,
+            name: "missing".to_string(),
+            textures: Default::default(),
+            tiles: [Tile::MISSING; 6],
+            frames: [1; 6],
+            frame_time: None,
+            tint: [None; 6],
+            solid: true,
+            render: RenderClass::Opaque,
+            shape: Shape::Cube,
+            placement: Placement::Fixed,
+            hardness: 1.,
+        }
+    }
+}
+
+/// One face of a block as drawn, see [`BlockDef::face`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceTexture {
+    pub tile: Tile,
+    pub tint: Option<Tint>,
+    /// See [`BlockDef::animation`].
+    pub animation: [f32; 2],
+    /// Turned a quarter so the texture follows the block's axis.
+    pub rotated: bool,
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Parse(ron::Error),
+    DuplicateId(u8),
+    DuplicateName(String),
+    MissingTexture { name: String, face: Face },
+    Model(String, serde_json::Error),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Parse(e) => write!(f, "invalid block registry: {}", e),
+            RegistryError::DuplicateId(id) => write!(f, "block id {} is defined twice", id),
+            RegistryError::DuplicateName(name) => write!(f, "block {} is defined twice", name),
+            RegistryError::MissingTexture { name, face } => {
+                write!(f, "block {} has no texture for {:?}", name, face)
+            }
+            RegistryError::Model(path, e) => write!(f, "invalid block model {}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<ron::Error> for RegistryError {
+    fn from(e: ron::Error) -> Self {
+        RegistryError::Parse(e)
+    }
+}
+
+/// Every block the game knows about, read from a `.blocks.ron` file so blocks can be added
+/// without touching the code. The default registry is `assets/default.blocks.ron`.
+/// Files need `#![enable(implicit_some)]` so optional fields can be written without `Some`.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "9f5d2c63-1b1e-4c0e-9a43-6d8f3a1e2b7c"]
+pub struct BlockRegistry {
+    blocks: Vec<Option<BlockDef>>,
+    names: HashMap<String, Block>,
+    missing: BlockDef,
+}
+
+impl BlockRegistry {
+    pub fn from_ron(source: &[u8]) -> Result<Self, RegistryError> {
+        let descs: Vec<BlockDesc> = ron::de::from_bytes(source)?;
+        let mut registry = Self {
+            blocks: vec![None; 256],
+            names: HashMap::default(),
+            missing: BlockDef::missing(),
+        };
+        for desc in descs {
+            let mut textures: [String; 6] = Default::default();
+            for face in Face::ALL {
+                let missing = || RegistryError::MissingTexture {
+                    name: desc.name.clone(),
+                    face,
+                };
+                textures[face.index()] = desc.textures.get(face).ok_or_else(missing)?;
+            }
+            let def = BlockDef {
+                id: desc.id,
+                name: desc.name,
+                textures,
+                tiles: [Tile::MISSING; 6],
+                frames: [1; 6],
+                frame_time: desc.frame_time,
+                tint: Face::ALL.map(|face| desc.tint.get(face)),
+                solid: desc.solid,
+                render: desc.render,
+                shape: desc.shape,
+                placement: desc.placement,
+                hardness: desc.hardness,
+            };
+
+            if registry.blocks[def.id as usize].is_some() {
+                return Err(RegistryError::DuplicateId(def.id));
+            }
+            if registry.names.contains_key(&def.name) {
+                return Err(RegistryError::DuplicateName(def.name));
+            }
+            registry.names.insert(def.name.clone(), Block::new(def.id));
+            let id = def.id as usize;
+            registry.blocks[id] = Some(def);
+        }
+        Ok(registry)
+    }
+    /// The definition of a block, or a placeholder if its id isn't registered.
+    pub fn get(&self, block: Block) -> &BlockDef {
+        self.blocks[block.id as usize]
+            .as_ref()
+            .unwrap_or(&self.missing)
+    }
+    pub fn by_name(&self, name: &str) -> Option<Block> {
+        self.names.get(name).copied()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &BlockDef> {
+        self.blocks.iter().flatten()
+    }
+    /// Every texture name blocks use, each once.
+    pub fn texture_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .iter()
+            .flat_map(|def| def.textures.iter().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    /// Points every block face at its texture's tile in `atlas`. Textures the atlas doesn't
+    /// have show the missing texture.
+    pub fn resolve_tiles(&mut self, atlas: &AtlasLayout) {
+        for def in self.blocks.iter_mut().flatten() {
+            for face in Face::ALL {
+                let name = &def.textures[face.index()];
+                let tile = atlas.get(name).unwrap_or(Tile::MISSING);
+                def.tiles[face.index()] = tile;
+                def.frames[face.index()] = atlas.frames(tile);
+            }
+        }
+    }
+    /// Paths of the model files blocks refer to, relative to the assets folder.
+    pub fn model_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .iter()
+            .filter_map(|def| match &def.shape {
+                Shape::Model(model) => Some(model.path.clone()),
+                _ => None,
+            })
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+    /// Gives every block using the model file at `path` its boxes.
+    pub fn set_model(&mut self, path: &str, source: &[u8]) -> Result<(), RegistryError> {
+        let model =
+            BoxModel::from_json(source).map_err(|e| RegistryError::Model(path.to_string(), e))?;
+        for def in self.blocks.iter_mut().flatten() {
+            if let Shape::Model(model_ref) = &mut def.shape {
+                if model_ref.path == path {
+                    model_ref.boxes = model.boxes();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Model files of the built-in registry, so it doesn't need the asset server either.
+const BUILTIN_MODELS: &[(&str, &[u8])] = &[(
+    "models/table.json",
+    include_bytes!("../assets/models/table.json"),
+)];
+
+/// The registry shipped with the game, so a world works before any asset is loaded.
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        let mut registry = Self::from_ron(include_bytes!("../assets/default.blocks.ron"))
+            .expect("built-in block registry is valid");
+        for (path, source) in BUILTIN_MODELS {
+            registry
+                .set_model(path, source)
+                .expect("built-in block models are valid");
+        }
+        registry
+    }
+}
+
+#[derive(Default)]
+pub struct BlockRegistryLoader;
+
+impl AssetLoader for BlockRegistryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut registry = BlockRegistry::from_ron(bytes)?;
+            for path in registry.model_paths() {
+                let source = load_context.read_asset_bytes(&path).await?;
+                registry.set_model(&path, &source)?;
             }
+            load_context.set_default_asset(LoadedAsset::new(registry));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["blocks.ron"]
+    }
+}
+
+/// Handle to the registry every voxel world uses, kept so the asset stays loaded and
+/// edits to the file are picked up.
+pub struct BlockRegistryHandle(pub Handle<BlockRegistry>);
+
+/// Loads `default.blocks.ron` and hands it to every [`VoxelWorld`](crate::world::VoxelWorld)
+/// whenever it changes. Add it once, like the material plugin.
+pub struct BlockRegistryPlugin;
+
+impl Plugin for BlockRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BlockRegistry>()
+            .init_asset_loader::<BlockRegistryLoader>()
+            .add_startup_system(load_registry);
+    }
+}
+
+fn load_registry(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(BlockRegistryHandle(assets.load("default.blocks.ron")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_registry() {
+        let registry = BlockRegistry::default();
+        let grass = registry.by_name("grass").unwrap();
+        let def = registry.get(grass);
+        assert_eq!("grass_top", def.texture(Face::PosY));
+        assert_eq!("dirt", def.texture(Face::NegY));
+        assert_eq!("grass_side", def.texture(Face::PosX));
+        assert!(def.tiles.iter().all(|t| *t == Tile::MISSING));
+        assert_eq!(Some(Tint::Grass), def.tint(Face::PosY));
+        assert_eq!(None, def.tint(Face::PosX));
+
+        // built-in models are read along with the blocks
+        let table = registry.get(registry.by_name("table").unwrap());
+        let state = BlockState::default();
+        assert_eq!(5, table.shape.boxes(state).len());
+        assert!(table.covers(Face::PosY, state) && !table.covers(Face::NegY, state));
+        assert_eq!(vec!["models/table.json"], registry.model_paths());
+
+        // every built-in texture has a file
+        for name in registry.texture_names() {
+            let path = format!("{}/assets/blocks/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+            assert!(std::path::Path::new(&path).exists(), "{} is missing", path);
+        }
+
+        // unknown ids still render as something
+        assert_eq!("missing", registry.get(Block::new(200)).name);
+        assert!(registry
+            .iter()
+            .all(|def| registry.get(Block::new(def.id)) == def));
+    }
+
+    #[test]
+    fn registry_errors() {
+        let parse = |s: &str| {
+            BlockRegistry::from_ron(format!("#![enable(implicit_some)]\n{}", s).as_bytes())
         };
-        [
-            // top
-            [uv[0][0][1] / 16., uv[0][1][1] / 16.],
-            [uv[0][0][0] / 16., uv[0][1][1] / 16.],
-            [uv[0][0][0] / 16., uv[0][1][0] / 16.],
-            [uv[0][0][1] / 16., uv[0][1][0] / 16.],
-            //bottom
-            [uv[1][0][0] / 16., uv[1][1][0] / 16.],
-            [uv[1][0][1] / 16., uv[1][1][0] / 16.],
-            [uv[1][0][1] / 16., uv[1][1][1] / 16.],
-            [uv[1][0][0] / 16., uv[1][1][1] / 16.],
-            //right
-            [uv[2][0][1] / 16., uv[2][1][1] / 16.],
-            [uv[2][0][1] / 16., uv[2][1][0] / 16.],
-            [uv[2][0][0] / 16., uv[2][1][0] / 16.],
-            [uv[2][0][0] / 16., uv[2][1][1] / 16.],
-            //left
-            [uv[3][0][1] / 16., uv[3][1][1] / 16.],
-            [uv[3][0][1] / 16., uv[3][1][0] / 16.],
-            [uv[3][0][0] / 16., uv[3][1][0] / 16.],
-            [uv[3][0][0] / 16., uv[3][1][1] / 16.],
-            //front
-            [uv[4][0][1] / 16., uv[4][1][0] / 16.],
-            [uv[4][0][0] / 16., uv[4][1][0] / 16.],
-            [uv[4][0][0] / 16., uv[4][1][1] / 16.],
-            [uv[4][0][1] / 16., uv[4][1][1] / 16.],
-            //back
-            [uv[5][0][0] / 16., uv[5][1][0] / 16.],
-            [uv[5][0][1] / 16., uv[5][1][0] / 16.],
-            [uv[5][0][1] / 16., uv[5][1][1] / 16.],
-            [uv[5][0][0] / 16., uv[5][1][1] / 16.],
-        ]
+        let registry = parse(
+            r#"[
+                (id: 3, name: "glass", textures: (all: "glass"), render: Translucent),
+                (id: 9, name: "crate", textures: (side: "crate", top: "lid", bottom: "lid")),
+                (id: 10, name: "lava", textures: (all: "lava"), frame_time: 0.5),
+            ]"#,
+        )
+        .unwrap();
+        let glass = registry.get(registry.by_name("glass").unwrap());
+        assert!(glass.solid && !glass.occludes());
+        assert!(glass.hides(glass));
+        let crate_ = registry.get(registry.by_name("crate").unwrap());
+        assert!(!glass.hides(crate_) && crate_.hides(glass));
+
+        let mut registry = registry;
+        assert_eq!(
+            vec!["crate", "glass", "lava", "lid"],
+            registry.texture_names()
+        );
+        let atlas = AtlasLayout::with_frames(
+            16,
+            [
+                ("lid".to_string(), 1),
+                ("crate".to_string(), 1),
+                ("lava".to_string(), 4),
+            ],
+        );
+        registry.resolve_tiles(&atlas);
+        let crate_ = registry.get(registry.by_name("crate").unwrap());
+        assert_eq!(atlas.get("lid"), Some(crate_.tile(Face::PosY)));
+        assert_eq!(atlas.get("crate"), Some(crate_.tile(Face::PosX)));
+        let glass = registry.get(registry.by_name("glass").unwrap());
+        assert_eq!(Tile::MISSING, glass.tile(Face::PosY));
+        assert_eq!([1., 1.], glass.animation(Face::PosY));
+        let lava = registry.get(registry.by_name("lava").unwrap());
+        assert_eq!([4., 0.5], lava.animation(Face::NegX));
+
+        assert!(matches!(
+            parse(r#"[(id: 1, name: "a", textures: (side: "a"))]"#),
+            Err(RegistryError::MissingTexture {
+                face: Face::PosY,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(
+                r#"[(id: 1, name: "a", textures: (all: "a")),
+                    (id: 1, name: "b", textures: (all: "b"))]"#
+            ),
+            Err(RegistryError::DuplicateId(1))
+        ));
+        assert!(matches!(parse("[(id: 1)]"), Err(RegistryError::Parse(_))));
     }
 }
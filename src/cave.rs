@@ -0,0 +1,138 @@
+use crate::chunk::Chunk;
+use crate::coords::{LocalPos, WorldPos, CHUNK_WIDTH};
+use crate::noise::Noise;
+
+/// Hollows caves out of generated terrain with 3D noise: wide "cheese" caverns where one
+/// noise runs high, and winding "spaghetti" tunnels along where two others both cross zero.
+#[derive(Debug, Clone)]
+pub struct CaveCarver {
+    cheese: Noise,
+    spaghetti: [Noise; 2],
+    /// Blocks across one cavern, roughly.
+    pub cheese_scale: f64,
+    /// Noise above which caverns open up. Higher makes them rarer and smaller.
+    pub cheese_threshold: f64,
+    /// Blocks along one bend of a tunnel, roughly.
+    pub spaghetti_scale: f64,
+    /// How close to zero both tunnel noises have to be. Higher makes tunnels wider.
+    pub spaghetti_width: f64,
+    /// Caves are stretched sideways by this much, so they run along more than down.
+    pub flatten: f64,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            cheese: Noise::new(seed.wrapping_add(0x68e3_1da4)),
+            spaghetti: [
+                Noise::new(seed.wrapping_add(0xb529_7a4d)),
+                Noise::new(seed.wrapping_add(0x1b56_c4e9)),
+            ],
+            cheese_scale: 64.,
+            cheese_threshold: 0.45,
+            spaghetti_scale: 48.,
+            spaghetti_width: 0.06,
+            flatten: 2.,
+        }
+    }
+    /// Whether the block at `x`, `y`, `z` is inside a cave.
+    pub fn is_cave(&self, x: i32, y: i32, z: i32) -> bool {
+        let (x, y, z) = (x as f64, y as f64 * self.flatten, z as f64);
+        let s = self.cheese_scale;
+        if self.cheese.fbm3(x / s, y / s, z / s, 2) > self.cheese_threshold {
+            return true;
+        }
+        let s = self.spaghetti_scale;
+        self.spaghetti
+            .iter()
+            .all(|noise| noise.get3(x / s, y / s, z / s).abs() < self.spaghetti_width)
+    }
+    /// Empties the cave blocks of `chunk`, leaving everything above the column's ceiling
+    /// alone. `ceilings` is indexed `x + z * CHUNK_WIDTH`.
+    pub fn carve(&self, chunk: &mut Chunk, ceilings: &[i32]) {
+        let origin = WorldPos::from(chunk.pos);
+        for local in LocalPos::all() {
+            let y = origin.y + local.y() as i32;
+            if y > ceilings[local.x() + local.z() * CHUNK_WIDTH as usize]
+                || chunk.get_block(local).is_none()
+            {
+                continue;
+            }
+            if self.is_cave(origin.x + local.x() as i32, y, origin.z + local.z() as i32) {
+                chunk.replace_block(local, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::chunk::ChunkGrid;
+    use crate::coords::{ChunkPos, CHUNK_VOLUME};
+    use crate::palette::PalettedBlocks;
+
+    fn stone_chunk(pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        chunk.blocks = PalettedBlocks::filled(Some(Block::new(1)));
+        chunk
+    }
+
+    #[test]
+    fn caves_open_up_inside_stone() {
+        let carver = CaveCarver::new(5);
+        let pos = ChunkPos::new(0, -2, 0);
+        let mut counts = vec![];
+        for x in 0..8 {
+            let mut chunk = stone_chunk(ChunkPos::new(x, -2, 0));
+            carver.carve(
+                &mut chunk,
+                &[i32::MAX; (CHUNK_WIDTH * CHUNK_WIDTH) as usize],
+            );
+            counts.push(chunk.blocks.iter().filter(|b| b.is_none()).count());
+        }
+        // some chunks get hollowed out, but caves never take over
+        assert!(counts.iter().any(|c| *c > 0), "no caves in {:?}", counts);
+        assert!(counts.iter().all(|c| *c < CHUNK_VOLUME / 2), "{:?}", counts);
+
+        // nothing above the ceilings is touched
+        let mut chunk = stone_chunk(pos);
+        let ceilings = [WorldPos::from(pos).y + 3; (CHUNK_WIDTH * CHUNK_WIDTH) as usize];
+        carver.carve(&mut chunk, &ceilings);
+        for local in LocalPos::all().filter(|l| l.y() > 3) {
+            assert!(chunk.is_block(local));
+        }
+    }
+
+    #[test]
+    fn cave_walls_are_exposed() {
+        let carver = CaveCarver::new(5);
+        let mut grid = ChunkGrid::new();
+        let positions: Vec<_> = (0..8).map(|x| ChunkPos::new(x, -2, 0)).collect();
+        for pos in &positions {
+            let mut chunk = stone_chunk(*pos);
+            carver.carve(
+                &mut chunk,
+                &[i32::MAX; (CHUNK_WIDTH * CHUNK_WIDTH) as usize],
+            );
+            grid.set_chunk(chunk);
+        }
+        let mut exposed = 0;
+        for pos in &positions {
+            let chunk = grid.get_chunk_from_coords(*pos).unwrap();
+            for local in LocalPos::all().filter(|l| chunk.is_block(*l)) {
+                let faces = grid.get_faces(*pos, local);
+                // a face is exposed exactly where the cave carved out its neighbour
+                for (open, (_, neighbour)) in faces.into_iter().zip(pos.world(local).neighbours()) {
+                    let (chunk, local) = neighbour.split();
+                    if let Some(neighbour) = grid.get_chunk_from_coords(chunk) {
+                        assert_eq!(!neighbour.is_block(local), open);
+                        exposed += open as usize;
+                    }
+                }
+            }
+        }
+        assert!(exposed > 0);
+    }
+}
@@ -1,272 +1,257 @@
-use bevy::prelude::*;
-use bevy::render::mesh::Indices;
-use bevy::render::render_resource::PrimitiveTopology;
-use bevy::tasks::Task;
-use spin::Mutex;
-use std::rc::Rc;
 use std::sync::Arc;
 
-use crate::block::Block;
-
-const CHUNK_SIZE: usize = 32 * 32 * 32;
-
-// pub struct ChunkGrid(pub Box<[Option<Chunk>; CHUNK_SIZE]>);
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::atlas::AtlasLayout;
+use crate::biome::Climate;
+use crate::block::{Block, BlockRegistry, RenderClass};
+use crate::coords::{ChunkPos, Face, LocalPos, WorldPos};
+use crate::mesh::{ChunkMeshData, ChunkSnapshot, Mesher};
+use crate::palette::PalettedBlocks;
+use crate::shape::Shape;
+use crate::state::BlockState;
+
+/// Sparse storage of every loaded chunk, keyed by chunk coordinates.
+/// Only chunks that exist take up memory, so the world is unbounded in every direction.
+/// Chunks are shared with meshing jobs and copied on write while a job holds them.
 pub struct ChunkGrid {
-    pub chunks: Box<[Option<Chunk>; CHUNK_SIZE]>,
-    pub queued_chunks: Vec<Chunk>,
+    pub chunks: HashMap<ChunkPos, Arc<Chunk>>,
+    /// Entity showing each chunk's mesh, once it has been spawned.
+    pub entities: HashMap<ChunkPos, Entity>,
+    /// Chunks removed since their entities were last despawned.
+    pub removed_chunks: Vec<ChunkPos>,
+    /// Colors grass and leaves depending on where they are.
+    pub climate: Climate,
+    /// What each block id looks like, shared with meshing jobs.
+    pub registry: Arc<BlockRegistry>,
+    /// Which texture array layer each block texture is in, already resolved into the registry.
+    pub atlas: Arc<AtlasLayout>,
 }
 
 impl ChunkGrid {
     pub fn new() -> Self {
         Self {
-            chunks: Box::new([Chunk::EMPTY; CHUNK_SIZE]),
-            queued_chunks: vec![],
+            chunks: HashMap::default(),
+            entities: HashMap::default(),
+            removed_chunks: vec![],
+            climate: Climate::default(),
+            registry: Arc::new(BlockRegistry::default()),
+            atlas: Arc::new(AtlasLayout::default()),
         }
     }
-    pub fn set_chunk(&mut self, chunk: Chunk) {
-        let x = chunk.x as isize;
-        let y = chunk.y as isize;
-        let z = chunk.z as isize;
-        let index = Self::chunk_coords_to_index(x, y, z);
-        info!("setting chunk at xyz: {}{}{} i: {}", x, y, z, index);
-        self.chunks[index] = Some(chunk);
-    }
-    pub fn add_to_queue(&mut self, task: &Chunk) {
-        self.queued_chunks.push(task.clone());
-    }
-    pub fn chunk_index_to_coords(index: usize) -> (usize, usize, usize) {
-        let index = index - (CHUNK_SIZE / 2);
-        let x = (index % 32) * 32; // 0..32 then resets to 0
-        let y = (index / (32 * 32)) * 32; // 0..1 is equal to a 32 * 32 block area
-        let z = ((index / 32) % 32) * 32;
-        // 0..1 is equal to a 32 block area
-        // info!("chunk index to coords: {} {:?}",index,(x,y,z));
-        (x, y, z)
-    }
-    pub fn chunk_coords_to_index(x: isize, y: isize, z: isize) -> usize {
-        let x1 = x; // incremental is the same
-        let y1 = y * 32 * 32; // incremental is 1:32*32
-        let z1 = z * 32; // incremental is 1:32
-                         // info!("chunk coord to index: {:?} {}",(x,y,z),i);
-        ((x1 + y1 + z1) + (CHUNK_SIZE / 2) as isize) as usize
-    }
-    pub fn get_faces(
-        &self,
-        c_x: isize,
-        c_y: isize,
-        c_z: isize,
-        b_x: usize,
-        b_y: usize,
-        b_z: usize,
-    ) -> [bool; 6] {
-        let (w_x, w_y, w_z) = Self::convert_to_world_coords(c_x, c_y, c_z, b_x, b_y, b_z);
-        // info!("world xyz: {} {} {}",w_x,w_y,w_z);
-        let top = {
-            let (w_x, w_y, w_z) = (w_x, w_y, w_z + 1);
-            let (c_x, c_y, c_z) = Self::convert_to_chunk_coords(w_x, w_y, w_z);
-            let (b_x, b_y, b_z) = Self::convert_to_block_coords(w_x, w_y, w_z);
-            if let Some(c) = self.get_chunk_from_coords(c_x, c_y, c_z) {
-                // info!("block xyz: {} {} {}",b_x,b_y,b_z);
-                !(c.is_block(b_x, b_y, b_z))
-            } else {
-                true
-            }
-        };
-        let bottom = {
-            let (w_x, w_y, w_z) = (w_x, w_y, w_z - 1);
-            let (c_x, c_y, c_z) = Self::convert_to_chunk_coords(w_x, w_y, w_z);
-            let (b_x, b_y, b_z) = Self::convert_to_block_coords(w_x, w_y, w_z);
-            if let Some(c) = self.get_chunk_from_coords(c_x, c_y, c_z) {
-                // info!("block xyz: {} {} {}",b_x,b_y,b_z);
-                !(c.is_block(b_x, b_y, b_z))
-            } else {
-                true
-            }
-        };
-        let right = {
-            let (w_x, w_y, w_z) = (w_x + 1, w_y, w_z);
-            let (c_x, c_y, c_z) = Self::convert_to_chunk_coords(w_x, w_y, w_z);
-            let (b_x, b_y, b_z) = Self::convert_to_block_coords(w_x, w_y, w_z);
-            if let Some(c) = self.get_chunk_from_coords(c_x, c_y, c_z) {
-                // info!("block xyz: {} {} {}",b_x,b_y,b_z);
-                !(c.is_block(b_x, b_y, b_z))
-            } else {
-                true
-            }
-        };
-        let left = {
-            let (w_x, w_y, w_z) = (w_x - 1, w_y, w_z);
-            let (c_x, c_y, c_z) = Self::convert_to_chunk_coords(w_x, w_y, w_z);
-            let (b_x, b_y, b_z) = Self::convert_to_block_coords(w_x, w_y, w_z);
-            if let Some(c) = self.get_chunk_from_coords(c_x, c_y, c_z) {
-                // info!("block xyz: {} {} {}",b_x,b_y,b_z);
-                !(c.is_block(b_x, b_y, b_z))
-            } else {
-                true
+    /// Swaps in a new block registry and remeshes everything with it.
+    pub fn set_registry(&mut self, mut registry: BlockRegistry) {
+        registry.resolve_tiles(&self.atlas);
+        self.registry = Arc::new(registry);
+        self.mark_all_dirty();
+    }
+    /// Swaps in a new atlas layout and remeshes everything with it.
+    pub fn set_atlas(&mut self, atlas: Arc<AtlasLayout>) {
+        let mut registry = BlockRegistry::clone(&self.registry);
+        registry.resolve_tiles(&atlas);
+        self.registry = Arc::new(registry);
+        self.atlas = atlas;
+        self.mark_all_dirty();
+    }
+    /// Inserts or replaces a chunk. Loaded neighbours are marked dirty, as the faces
+    /// bordering the new chunk may have become hidden or exposed. Blocks that were
+    /// overwritten while filling the chunk are dropped from its palette.
+    pub fn set_chunk(&mut self, mut chunk: Chunk) {
+        trace!("setting chunk at {:?}", chunk.pos);
+        chunk.blocks.compact();
+        self.mark_neighbours_dirty(chunk.pos);
+        self.chunks.insert(chunk.pos, Arc::new(chunk));
+    }
+    /// Unloads a chunk. Its entity is despawned the next time the world's systems run.
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> Option<Arc<Chunk>> {
+        let chunk = self.chunks.remove(&pos)?;
+        trace!("removing chunk at {:?}", pos);
+        self.mark_neighbours_dirty(pos);
+        self.removed_chunks.push(pos);
+        Some(chunk)
+    }
+    pub fn chunk_entity(&self, pos: ChunkPos) -> Option<Entity> {
+        self.entities.get(&pos).copied()
+    }
+    /// Marks every chunk dirty, e.g. after switching how chunks are meshed.
+    pub fn mark_all_dirty(&mut self) {
+        for c in self.chunks.values_mut() {
+            Arc::make_mut(c).dirty = true;
+        }
+    }
+    fn mark_neighbours_dirty(&mut self, pos: ChunkPos) {
+        // ambient occlusion looks across edges and corners, not just faces
+        for neighbour in pos.surrounding() {
+            if let Some(c) = self.get_chunk_mut(neighbour) {
+                c.dirty = true;
             }
+        }
+    }
+    /// Which faces of the block are exposed, in [`Face::ALL`] order, by the same rule the
+    /// mesher culls with, see [`BlockDef::hides_face`](crate::block::BlockDef::hides_face).
+    /// Faces bordering a chunk that isn't loaded count as exposed, air has none.
+    pub fn get_faces(&self, chunk: ChunkPos, local: LocalPos) -> [bool; 6] {
+        let block = match self
+            .get_chunk_from_coords(chunk)
+            .and_then(|c| c.get_block(local))
+        {
+            Some(b) => self.registry.get(b),
+            None => return [false; 6],
         };
-        let front = {
-            let (w_x, w_y, w_z) = (w_x, w_y + 1, w_z);
-            let (c_x, c_y, c_z) = Self::convert_to_chunk_coords(w_x, w_y, w_z);
-            let (b_x, b_y, b_z) = Self::convert_to_block_coords(w_x, w_y, w_z);
-            if let Some(c) = self.get_chunk_from_coords(c_x, c_y, c_z) {
-                // info!("block xyz: {} {} {}",b_x,b_y,b_z);
-                !(c.is_block(b_x, b_y, b_z))
+        let pos = chunk.world(local);
+        Face::ALL.map(|face| {
+            let (chunk, local) = pos.neighbour(face).split();
+            !self
+                .get_chunk_from_coords(chunk)
+                .and_then(|c| c.get_block(local))
+                .is_some_and(|n| self.registry.get(n).hides_face(n.state, face, block))
+        })
+    }
+    /// Copies out everything needed to mesh a chunk off the main thread.
+    pub fn snapshot(&self, pos: ChunkPos) -> Option<ChunkSnapshot> {
+        let chunk = self.chunks.get(&pos)?.clone();
+        let mut neighbours: [Option<Arc<Chunk>>; 27] = Default::default();
+        for neighbour in pos.surrounding() {
+            neighbours[ChunkSnapshot::neighbour_index(neighbour - pos)] =
+                self.chunks.get(&neighbour).cloned();
+        }
+        Some(ChunkSnapshot {
+            chunk,
+            neighbours,
+            climate: self.climate,
+            registry: self.registry.clone(),
+        })
+    }
+    pub fn generate_chunk_data(&self, pos: ChunkPos, mesher: Mesher) -> ChunkMeshData {
+        self.snapshot(pos)
+            .map(|s| mesher.mesh(&s))
+            .unwrap_or_default()
+    }
+    pub fn get_chunk_from_coords(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos).map(Arc::as_ref)
+    }
+    /// Mutable access to a chunk. Copies it first if a meshing job still holds it.
+    pub fn get_chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos).map(Arc::make_mut)
+    }
+    pub fn get_block(&self, pos: WorldPos) -> Option<Block> {
+        let (chunk, local) = pos.split();
+        self.get_chunk_from_coords(chunk)
+            .and_then(|c| c.get_block(local))
+    }
+    /// Places a block, creating its chunk if it isn't loaded yet. Returns the block it replaced.
+    pub fn set_block(&mut self, pos: WorldPos, block: Block) -> Option<Block> {
+        let (chunk, local) = pos.split();
+        let previous = Arc::make_mut(
+            self.chunks
+                .entry(chunk)
+                .or_insert_with(|| Arc::new(Chunk::new(chunk))),
+        )
+        .replace_block(local, Some(block));
+        if previous != Some(block) {
+            self.mark_edited(chunk, local);
+        }
+        previous
+    }
+    /// Removes a block and returns it. Never creates a chunk.
+    pub fn remove_block(&mut self, pos: WorldPos) -> Option<Block> {
+        let (chunk, local) = pos.split();
+        let previous = self
+            .get_chunk_mut(chunk)
+            .and_then(|c| c.replace_block(local, None));
+        if previous.is_some() {
+            self.mark_edited(chunk, local);
+        }
+        previous
+    }
+    /// Places `block` against `face` of the block at `against` the way a player does: its
+    /// state follows its [`Placement`](crate::state::Placement) and it keeps the water it's
+    /// placed into unless it fills the whole block. Returns where it went, or `None` if
+    /// that spot is taken.
+    pub fn place_block(
+        &mut self,
+        against: WorldPos,
+        face: Face,
+        block: Block,
+        look: Vec3,
+    ) -> Option<WorldPos> {
+        let pos = against.neighbour(face);
+        let replaced = self.get_block(pos).map(|b| self.registry.get(b).render);
+        if replaced.is_some_and(|render| render != RenderClass::Liquid) {
+            return None;
+        }
+        let def = self.registry.get(block);
+        let state = BlockState::placed(def.placement, face, look)
+            .with_waterlogged(replaced.is_some() && def.shape != Shape::Cube);
+        self.set_block(pos, block.with_state(state));
+        Some(pos)
+    }
+    /// The first block a ray from `origin` hits within `max_distance` blocks, and the face
+    /// it enters that block through. Steps from block to block, so it never skips a corner.
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<(WorldPos, Face)> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return None;
+        }
+        // blocks are centred on their coordinates, so their boundaries are at .5
+        let start = origin + Vec3::splat(0.5);
+        let mut pos = start.floor().as_ivec3();
+        let step = direction.signum().as_ivec3();
+        let delta = direction.abs().recip();
+        let mut next = Vec3::ZERO;
+        for axis in 0..3 {
+            let to_boundary = if direction[axis] > 0. {
+                pos[axis] as f32 + 1. - start[axis]
             } else {
-                true
-            }
-        };
-        let back = {
-            let (w_x, w_y, w_z) = (w_x, w_y - 1, w_z);
-            let (c_x, c_y, c_z) = Self::convert_to_chunk_coords(w_x, w_y, w_z);
-            let (b_x, b_y, b_z) = Self::convert_to_block_coords(w_x, w_y, w_z);
-            if let Some(c) = self.get_chunk_from_coords(c_x, c_y, c_z) {
-                // info!("block xyz: {} {} {}",b_x,b_y,b_z);
-                !(c.is_block(b_x, b_y, b_z))
+                start[axis] - pos[axis] as f32
+            };
+            next[axis] = to_boundary * delta[axis];
+        }
+        loop {
+            let axis = if next.x < next.y && next.x < next.z {
+                0
+            } else if next.y < next.z {
+                1
             } else {
-                true
+                2
+            };
+            if next[axis] > max_distance {
+                return None;
             }
-        };
-        [top, bottom, right, left, front, back]
-    }
-    pub fn generate_mesh(&self) -> Mesh {
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        let mut positions = Vec::with_capacity(32 * 32 * 32 * 32); //max
-        let mut normals = Vec::with_capacity(32 * 32 * 32 * 32); //max
-        let mut uvs = Vec::with_capacity(32 * 32 * 32 * 32); //max
-        let mut indices = Vec::with_capacity(32 * 32 * 32 * 32);
-        for c in self.chunks.iter() {
-            if let Some(c) = c {
-                if !c.spawned {
-                    let (pos, normal, uv, indice) = self.generate_chunk_data(c);
-                    positions.extend(pos);
-                    normals.extend(normal);
-                    uvs.extend(uv);
-                    indices.extend(indice);
-                }
+            pos[axis] += step[axis];
+            next[axis] += delta[axis];
+            if self.get_block(WorldPos::from(pos)).is_some() {
+                let face = Face::ALL
+                    .into_iter()
+                    .find(|f| f.offset()[axis] == -step[axis])
+                    .unwrap();
+                return Some((WorldPos::from(pos), face));
             }
         }
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh
-    }
-    pub fn generate_chunk_mesh(&self, chunk: &Chunk) -> Mesh {
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        let (positions, normals, uvs, indices) = self.generate_chunk_data(chunk);
-
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        //TODO per vertex color for grass, should add color to generate_chunk_data
-        // mesh.set_attribute(Mesh::ATTRIBUTE_COLOR,[]);
-        mesh.set_indices(Some(Indices::U32(indices)));
-        return mesh;
-    }
-    pub fn generate_chunk_data(
-        &self,
-        chunk: &Chunk,
-    ) -> (Vec<[f32; 3]>, Vec<[f32; 3]>, Vec<[f32; 2]>, Vec<u32>) {
-        let mut positions = Vec::with_capacity(32 * 32 * 32); //max
-        let mut normals = Vec::with_capacity(32 * 32 * 32); //max
-        let mut uvs = Vec::with_capacity(32 * 32 * 32); //max
-        let mut indices = Vec::with_capacity(32 * 32 * 32);
-        info!(
-            "generating mesh with xyz: {} {} {}",
-            chunk.x, chunk.y, chunk.z
-        );
-        chunk.blocks.iter().enumerate().for_each(|(i, b)| {
-            match b {
-                None => {}
-                Some(b) => {
-                    let (block_x, block_y, block_z) = Chunk::index_to_coords(i);
-                    let faces: [bool; 6] = self.get_faces(
-                        chunk.x as isize,
-                        chunk.y as isize,
-                        chunk.z as isize,
-                        block_x,
-                        block_y,
-                        block_z,
-                    );
-                    // info!("faces: {:?}",faces);
-                    let uv = b.get_texture_uv();
-
-                    for (index, (position, normal)) in VERTICES.iter().enumerate() {
-                        let (x, y, z) = Chunk::index_to_coords(i);
-                        let position = [
-                            position[0] + (x as isize + (32 * chunk.x as isize)) as f32,
-                            position[1] + (y as isize + (32 * chunk.y as isize)) as f32,
-                            position[2] + (z as isize + (32 * chunk.z as isize)) as f32,
-                        ];
-                        if faces[index / 4] {
-                            positions.push(position);
-                            normals.push(*normal);
-                            uvs.push(uv[index]);
-                        } else {
-                            positions.push([0., 0., 0.]);
-                            normals.push([0., 0., 0.]);
-                            uvs.push([0., 0.]);
-                        }
-                    }
-                    for x in 0..6 {
-                        if faces[x] {
-                            let temp = &mut INDICES[x * 6..(x * 6) + 6];
-                            for u in temp.iter_mut() {
-                                *u += (i * 24) as u32;
-                            }
-                            indices.extend_from_slice(temp);
-                        } else {
-                            indices.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
-                        }
+    }
+    /// Marks the chunk owning `local` dirty, plus every neighbour chunk the block touches,
+    /// including across edges and corners since those shade the neighbour's faces.
+    fn mark_edited(&mut self, chunk: ChunkPos, local: LocalPos) {
+        if let Some(c) = self.get_chunk_mut(chunk) {
+            c.edited = true;
+        }
+        let pos = chunk.world(local);
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let touched = ChunkPos::from(pos + IVec3::new(x, y, z));
+                    if let Some(c) = self.get_chunk_mut(touched) {
+                        c.dirty = true;
                     }
                 }
             }
-        });
-        (positions, normals, uvs, indices)
-    }
-    pub fn block_coords_in_chunk(x: isize, y: isize, z: isize) -> (usize, usize, usize) {
-        (
-            (x % 32) as usize,
-            (y % (32 * 32 * 32)) as usize,
-            (z % (32 * 32)) as usize,
-        )
-    }
-    pub fn get_chunk_from_coords(&self, x: isize, y: isize, z: isize) -> &Option<Chunk> {
-        let index = Self::chunk_coords_to_index(x, y, z) as isize;
-        if (0..(32 * 32 * 32)).contains(&index) {
-            return &self.chunks[index as usize];
         }
-        &None
-    }
-
-    fn convert_to_world_coords(
-        c_x: isize,
-        c_y: isize,
-        c_z: isize,
-        b_x: usize,
-        b_y: usize,
-        b_z: usize,
-    ) -> (isize, isize, isize) {
-        (
-            (c_x * 32) + b_x as isize,
-            (c_y * 32) + b_y as isize,
-            (c_z * 32) + b_z as isize,
-        )
-    }
-    fn convert_to_block_coords(w_x: isize, w_y: isize, w_z: isize) -> (usize, usize, usize) {
-        (
-            if w_x < 0 { 32 + (w_x % 32) } else { w_x % 32 } as usize,
-            if w_y < 0 { 32 + (w_y % 32) } else { w_y % 32 } as usize,
-            if w_z < 0 { 32 + (w_z % 32) } else { w_z % 32 } as usize,
-        )
-    }
-    fn convert_to_chunk_coords(w_x: isize, w_y: isize, w_z: isize) -> (isize, isize, isize) {
-        (
-            if w_x < 0 { (w_x / 32) - 1 } else { w_x / 32 },
-            if w_y < 0 { (w_y / 32) - 1 } else { w_y / 32 },
-            if w_z < 0 { (w_z / 32) - 1 } else { w_z / 32 },
-        )
     }
 }
 
@@ -278,123 +263,51 @@ impl Default for ChunkGrid {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Chunk {
-    pub x: i16,
-    pub y: i16,
-    pub z: i16,
-    pub blocks: Box<[Option<Block>; CHUNK_SIZE]>,
+    pub pos: ChunkPos,
+    pub blocks: PalettedBlocks,
     pub spawned: bool,
+    /// Set when a block in or next to this chunk changed since it was last meshed.
+    pub dirty: bool,
+    /// Set once a block in this chunk was placed or removed through the [`ChunkGrid`].
+    pub edited: bool,
 }
 
 impl Chunk {
-    pub fn new(x: i16, y: i16, z: i16) -> Self {
+    pub fn new(pos: ChunkPos) -> Self {
         Self {
-            blocks: Box::new([Block::EMPTY; 32 * 32 * 32]),
+            blocks: PalettedBlocks::default(),
             spawned: false,
-            x,
-            y,
-            z,
+            dirty: false,
+            edited: false,
+            pos,
         }
     }
-    pub fn set_block(&mut self, block: Block, x: usize, y: usize, z: usize) {
-        self.blocks[Self::coords_to_index(x as u16, y as u16, z as u16)] = Some(block);
-    }
-    pub fn get_faces(&self, block_index: usize) -> [bool; 6] {
-        let (x, y, z) = Self::index_to_coords(block_index);
-        [
-            self.is_block(x, y, z.wrapping_add(1)), // top
-            self.is_block(x, y, z.wrapping_sub(1)), // bottom
-            self.is_block(x.wrapping_add(1), y, z), // right
-            self.is_block(x.wrapping_sub(1), y, z), // left
-            self.is_block(x, y.wrapping_add(1), z), // front
-            self.is_block(x, y.wrapping_sub(1), z), // back
-        ]
-    }
-    pub fn get_block(&self, x: usize, y: usize, z: usize) -> &Option<Block> {
-        if (0..32).contains(&(x as i32))
-            && (0..32).contains(&(y as i32))
-            && (0..32).contains(&(z as i32))
-        {
-            // not even allowed to go beyond u16 size
-            &self.blocks[Self::coords_to_index(x as u16, y as u16, z as u16)]
-        } else {
-            &None
-        }
+    pub fn set_block(&mut self, block: Block, pos: LocalPos) {
+        self.blocks.set(pos.index(), Some(block));
     }
-    pub fn is_block(&self, x: usize, y: usize, z: usize) -> bool {
-        if (0..32).contains(&(x as i32))
-            && (0..32).contains(&(y as i32))
-            && (0..32).contains(&(z as i32))
-        {
-            // not even allowed to go beyond u16 size
-            self.blocks[Self::coords_to_index(x as u16, y as u16, z as u16)] != None
-        } else {
-            false
-        }
+    pub fn replace_block(&mut self, pos: LocalPos, block: Option<Block>) -> Option<Block> {
+        self.blocks.set(pos.index(), block)
     }
-    pub fn index_to_coords(index: usize) -> (usize, usize, usize) {
-        let x = index % 32; // 0..32 then resets to 0
-        let y = index / (32 * 32); // 0..1 is equal to a 32 * 32 block area
-        let z = (index / 32) % 32; // 0..1 is equal to a 32 block area
-        (x, y, z)
+    /// Which neighbours inside this chunk are blocks, in [`Face::ALL`] order.
+    pub fn get_faces(&self, pos: LocalPos) -> [bool; 6] {
+        Face::ALL.map(|face| pos.neighbour(face).is_some_and(|n| self.is_block(n)))
     }
-    pub fn coords_to_index(x: u16, y: u16, z: u16) -> usize {
-        let x = x; // incremental is the same
-        let y = y * 32 * 32; // incremental is 1:32*32
-        let z = z * 32; // incremental is 1:32
-        (x + y + z) as usize
+    pub fn get_block(&self, pos: LocalPos) -> Option<Block> {
+        self.blocks.get(pos.index())
+    }
+    pub fn is_block(&self, pos: LocalPos) -> bool {
+        self.blocks.get(pos.index()).is_some()
     }
-
-    const EMPTY: Option<Chunk> = None;
 }
 
-const INDICES: [u32; 36] = [
-    0, 1, 2, 2, 3, 0, // top
-    4, 5, 6, 6, 7, 4, // bottom
-    8, 9, 10, 10, 11, 8, // right
-    12, 13, 14, 14, 15, 12, // left
-    16, 17, 18, 18, 19, 16, // front
-    20, 21, 22, 22, 23, 20, // back
-];
-
-const VERTICES: &[([f32; 3], [f32; 3]); 24] = &[
-    // Top
-    ([-0.5, -0.5, 0.5], [0., 0., 1.0]),
-    ([0.5, -0.5, 0.5], [0., 0., 1.0]),
-    ([0.5, 0.5, 0.5], [0., 0., 1.0]),
-    ([-0.5, 0.5, 0.5], [0., 0., 1.0]),
-    // Bottom
-    ([-0.5, 0.5, -0.5], [0., 0., -1.0]),
-    ([0.5, 0.5, -0.5], [0., 0., -1.0]),
-    ([0.5, -0.5, -0.5], [0., 0., -1.0]),
-    ([-0.5, -0.5, -0.5], [0., 0., -1.0]),
-    // Right
-    ([0.5, -0.5, -0.5], [1.0, 0., 0.]),
-    ([0.5, 0.5, -0.5], [1.0, 0., 0.]),
-    ([0.5, 0.5, 0.5], [1.0, 0., 0.]),
-    ([0.5, -0.5, 0.5], [1.0, 0., 0.]),
-    // Left
-    ([-0.5, -0.5, 0.5], [-1.0, 0., 0.]),
-    ([-0.5, 0.5, 0.5], [-1.0, 0., 0.]),
-    ([-0.5, 0.5, -0.5], [-1.0, 0., 0.]),
-    ([-0.5, -0.5, -0.5], [-1.0, 0., 0.]),
-    // Front
-    ([0.5, 0.5, -0.5], [0., 1.0, 0.]),
-    ([-0.5, 0.5, -0.5], [0., 1.0, 0.]),
-    ([-0.5, 0.5, 0.5], [0., 1.0, 0.]),
-    ([0.5, 0.5, 0.5], [0., 1.0, 0.]),
-    // Back
-    ([0.5, -0.5, 0.5], [0., -1.0, 0.]),
-    ([-0.5, -0.5, 0.5], [0., -1.0, 0.]),
-    ([-0.5, -0.5, -0.5], [0., -1.0, 0.]),
-    ([0.5, -0.5, -0.5], [0., -1.0, 0.]),
-];
-
 #[cfg(test)]
 mod tests {
-    use std::mem::size_of_val;
+    use std::mem::{size_of, size_of_val};
     use std::time::Instant;
 
     use super::*;
+    use crate::coords::CHUNK_VOLUME;
+    use crate::state::Axis;
 
     #[test]
     fn block_size() {
@@ -538,47 +451,240 @@ mod tests {
 
     #[test]
     fn chunk_calculations() {
-        assert_eq!(0, Chunk::coords_to_index(0, 0, 0));
-        assert_eq!(1, Chunk::coords_to_index(1, 0, 0));
-        assert_eq!(32, Chunk::coords_to_index(0, 0, 1));
-        assert_eq!(1024, Chunk::coords_to_index(0, 1, 0));
-        assert_eq!(1025, Chunk::coords_to_index(1, 1, 0));
+        assert_eq!(0, LocalPos::new(0, 0, 0).index());
+        assert_eq!(1, LocalPos::new(1, 0, 0).index());
+        assert_eq!(32, LocalPos::new(0, 0, 1).index());
+        assert_eq!(1024, LocalPos::new(0, 1, 0).index());
+        assert_eq!(1025, LocalPos::new(1, 1, 0).index());
+
+        assert_eq!(LocalPos::new(0, 0, 0), LocalPos::from_index(0));
+        assert_eq!(LocalPos::new(1, 0, 0), LocalPos::from_index(1));
+        assert_eq!(LocalPos::new(0, 0, 1), LocalPos::from_index(32));
+        assert_eq!(LocalPos::new(0, 1, 0), LocalPos::from_index(1024));
+        assert_eq!(LocalPos::new(1, 1, 0), LocalPos::from_index(1025));
+
+        let mut c = Chunk::new(ChunkPos::new(0, 0, 0));
+
+        c.set_block(Block::new(0), LocalPos::new(1, 2, 3));
+        assert_ne!(None, c.get_block(LocalPos::new(1, 2, 3)));
+        assert_eq!(Some(Block::new(0)), c.get_block(LocalPos::new(1, 2, 3)));
+        assert_eq!(2145, LocalPos::new(1, 2, 3).index());
+        assert_eq!(LocalPos::new(1, 2, 3), LocalPos::from_index(2145));
+
+        let mut c = Chunk::new(ChunkPos::new(0, 0, 0));
+
+        c.set_block(Block::new(0), LocalPos::new(31, 30, 29));
+        assert_ne!(None, c.get_block(LocalPos::new(31, 30, 29)));
+        assert_eq!(Some(Block::new(0)), c.get_block(LocalPos::new(31, 30, 29)));
+        assert_eq!(31679, LocalPos::new(31, 30, 29).index());
+        assert_eq!(LocalPos::new(31, 30, 29), LocalPos::from_index(31679));
+    }
 
-        assert_eq!((0, 0, 0), Chunk::index_to_coords(0));
-        assert_eq!((1, 0, 0), Chunk::index_to_coords(1));
-        assert_eq!((0, 0, 1), Chunk::index_to_coords(32));
-        assert_eq!((0, 1, 0), Chunk::index_to_coords(1024));
-        assert_eq!((1, 1, 0), Chunk::index_to_coords(1025));
+    #[test]
+    fn chunkgrid_calculations() {
+        let mut grid = ChunkGrid::new();
+        let local = LocalPos::new(1, 2, 3);
+        assert!(grid.get_chunk_from_coords(ChunkPos::new(0, 0, 0)).is_none());
+
+        // far beyond the old 32x32x32 bounds, in every direction
+        for (x, y, z) in [(0, 0, 0), (-1, 0, 0), (1000, -1000, 5), (-40000, 3, 40000)] {
+            let mut c = Chunk::new(ChunkPos::new(x, y, z));
+            c.set_block(Block::new(0), local);
+            grid.set_chunk(c);
+        }
+        assert_eq!(4, grid.chunks.len());
+        let pos = ChunkPos::new(-40000, 3, 40000);
+        let c = grid.get_chunk_from_coords(pos).unwrap();
+        assert_eq!(pos, c.pos);
+        assert_eq!(Some(Block::new(0)), c.get_block(local));
+
+        // coordinates no longer wrap onto another chunk
+        assert!(grid.get_chunk_from_coords(ChunkPos::new(1, 0, 0)).is_none());
+        assert!(grid.get_chunk_from_coords(ChunkPos::new(0, 0, 1)).is_none());
+
+        // setting a chunk at an existing position replaces it
+        let pos = ChunkPos::new(-1, 0, 0);
+        grid.set_chunk(Chunk::new(pos));
+        assert_eq!(4, grid.chunks.len());
+        assert_eq!(
+            None,
+            grid.get_chunk_from_coords(pos).unwrap().get_block(local)
+        );
+    }
+
+    #[test]
+    fn faces_across_chunks() {
+        let mut grid = ChunkGrid::new();
+        let mut a = Chunk::new(ChunkPos::new(0, 0, 0));
+        a.set_block(Block::new(0), LocalPos::new(31, 0, 0));
+        let mut b = Chunk::new(ChunkPos::new(1, 0, 0));
+        b.set_block(Block::new(0), LocalPos::new(0, 0, 0));
+        grid.set_chunk(a);
+        grid.set_chunk(b);
+
+        let faces = grid.get_faces(ChunkPos::new(0, 0, 0), LocalPos::new(31, 0, 0));
+        assert!(!faces[Face::PosX.index()]);
+        assert!(faces[Face::NegX.index()]);
+        // no chunk at y = -1, so the face is exposed
+        assert!(faces[Face::NegY.index()]);
+
+        // only the full bottom of a slab hides the block below it
+        let slab = grid.registry.by_name("slab").unwrap();
+        grid.set_block(WorldPos::new(31, 1, 0), slab);
+        grid.set_block(WorldPos::new(31, 2, 0), Block::new(0));
+        let (chunk, local) = WorldPos::new(31, 0, 0).split();
+        assert!(!grid.get_faces(chunk, local)[Face::PosY.index()]);
+        let (chunk, local) = WorldPos::new(31, 2, 0).split();
+        assert!(grid.get_faces(chunk, local)[Face::NegY.index()]);
+
+        // glass hides glass, but not the stone next to it
+        let glass = grid.registry.by_name("glass").unwrap();
+        grid.set_block(WorldPos::new(32, 5, 0), glass);
+        grid.set_block(WorldPos::new(33, 5, 0), glass);
+        grid.set_block(WorldPos::new(32, 6, 0), Block::new(1));
+        let (chunk, local) = WorldPos::new(32, 5, 0).split();
+        let faces = grid.get_faces(chunk, local);
+        assert!(!faces[Face::PosX.index()]);
+        assert!(!faces[Face::PosY.index()]);
+        let (chunk, local) = WorldPos::new(32, 6, 0).split();
+        assert!(grid.get_faces(chunk, local)[Face::NegY.index()]);
+        // air has no faces
+        assert_eq!(
+            [false; 6],
+            grid.get_faces(ChunkPos::new(1, 0, 0), LocalPos::new(5, 5, 5))
+        );
+    }
+
+    #[test]
+    fn world_block_access() {
+        let mut grid = ChunkGrid::new();
+        let pos = WorldPos::new(-1, 40, 5);
+        assert_eq!(None, grid.get_block(pos));
+        assert_eq!(None, grid.remove_block(pos));
+        assert!(grid.chunks.is_empty());
+
+        // chunks are created on demand
+        assert_eq!(None, grid.set_block(pos, Block::new(1)));
+        assert_eq!(Some(Block::new(1)), grid.get_block(pos));
+        let c = grid.get_chunk_from_coords(ChunkPos::new(-1, 1, 0)).unwrap();
+        assert_eq!(Some(Block::new(1)), c.get_block(LocalPos::new(31, 8, 5)));
+
+        assert_eq!(Some(Block::new(1)), grid.set_block(pos, Block::new(2)));
+        assert_eq!(Some(Block::new(2)), grid.remove_block(pos));
+        assert_eq!(None, grid.get_block(pos));
+    }
+
+    #[test]
+    fn block_changes_mark_chunks_dirty() {
+        let mut grid = ChunkGrid::new();
+        for (_, pos) in ChunkPos::default().neighbours() {
+            grid.set_chunk(Chunk::new(pos));
+        }
+        let dirty = |grid: &ChunkGrid| {
+            let mut dirty: Vec<_> = grid
+                .chunks
+                .values()
+                .filter(|c| c.dirty)
+                .map(|c| c.pos)
+                .collect();
+            dirty.sort_by_key(|p| (p.x, p.y, p.z));
+            dirty
+        };
 
-        let mut c = Chunk::new(0, 0, 0);
+        // loading the neighbours marked each other dirty
+        grid.chunks
+            .values_mut()
+            .for_each(|c| Arc::make_mut(c).dirty = false);
+
+        // inner block only touches its own chunk
+        grid.set_block(WorldPos::new(5, 5, 5), Block::new(1));
+        assert_eq!(vec![ChunkPos::new(0, 0, 0)], dirty(&grid));
+        grid.chunks
+            .values_mut()
+            .for_each(|c| Arc::make_mut(c).dirty = false);
+
+        // corner block borders three neighbours
+        grid.set_block(WorldPos::new(0, 31, 0), Block::new(1));
+        assert_eq!(
+            vec![
+                ChunkPos::new(-1, 0, 0),
+                ChunkPos::new(0, 0, -1),
+                ChunkPos::new(0, 0, 0),
+                ChunkPos::new(0, 1, 0),
+            ],
+            dirty(&grid)
+        );
+        grid.chunks
+            .values_mut()
+            .for_each(|c| Arc::make_mut(c).dirty = false);
 
-        c.set_block(Block(0), 1, 2, 3);
-        assert_ne!(None, *c.get_block(1, 2, 3));
-        assert_eq!(Some(Block(0)), *c.get_block(1, 2, 3));
-        assert_eq!(2145, Chunk::coords_to_index(1, 2, 3));
-        assert_eq!((1, 2, 3), Chunk::index_to_coords(2145));
+        // replacing a block with itself changes nothing
+        grid.set_block(WorldPos::new(0, 31, 0), Block::new(1));
+        assert!(dirty(&grid).is_empty());
+    }
 
-        let mut c = Chunk::new(0, 0, 0);
+    #[test]
+    fn placing_blocks() {
+        let mut grid = ChunkGrid::new();
+        let block = |name| grid.registry.by_name(name).unwrap();
+        let (stone, log, slab, water) =
+            (block("stone"), block("log"), block("slab"), block("water"));
+        grid.set_block(WorldPos::new(0, 0, 0), stone);
+        grid.set_block(WorldPos::new(0, 1, 0), water);
+
+        let down = Vec3::new(0.1, -1., 0.05);
+        let hit = grid.raycast(Vec3::new(-1.2, 4., 0.), Vec3::new(1.2, -4., 0.), 8.);
+        assert_eq!(Some((WorldPos::new(0, 1, 0), Face::PosY)), hit);
+        assert_eq!(None, grid.raycast(Vec3::new(0., 4., 0.), Vec3::Y, 8.));
+        assert_eq!(None, grid.raycast(Vec3::new(0., 12., 0.), -Vec3::Y, 8.));
+
+        // logs lie along the clicked face
+        let pos = grid.place_block(WorldPos::new(0, 0, 0), Face::NegX, log, down);
+        assert_eq!(Some(WorldPos::new(-1, 0, 0)), pos);
+        assert_eq!(Axis::X, grid.get_block(pos.unwrap()).unwrap().state.axis());
+        assert_eq!(
+            None,
+            grid.place_block(WorldPos::new(0, 0, 0), Face::NegX, log, down)
+        );
 
-        c.set_block(Block(0), 31, 30, 29);
-        assert_ne!(None, *c.get_block(31, 30, 29));
-        assert_eq!(Some(Block(0)), *c.get_block(31, 30, 29));
-        assert_eq!(31679, Chunk::coords_to_index(31, 30, 29));
-        assert_eq!((31, 30, 29), Chunk::index_to_coords(31679));
+        // slabs keep the water they replace
+        let pos = grid.place_block(WorldPos::new(0, 0, 0), Face::PosY, slab, down);
+        let placed = grid.get_block(pos.unwrap()).unwrap();
+        assert_eq!(slab.id, placed.id);
+        assert!(placed.state.waterlogged());
     }
 
     #[test]
-    fn chunkgrid_calculations() {
-        // assert_eq!(0, ChunkGrid::coords_to_index(0, 0, 0));
-        // assert_eq!(1, ChunkGrid::coords_to_index(1, 0, 0));
-        // assert_eq!(32, ChunkGrid::coords_to_index(0, 0, 1));
-        // assert_eq!(1024, ChunkGrid::coords_to_index(0, 1, 0));
-        // assert_eq!(1025, ChunkGrid::coords_to_index(1, 1, 0));
-
-        assert_eq!((0, 0, 0), ChunkGrid::chunk_index_to_coords(0));
-        assert_eq!((32, 0, 0), ChunkGrid::chunk_index_to_coords(1));
-        assert_eq!((0, 0, 32), ChunkGrid::chunk_index_to_coords(32));
-        assert_eq!((0, 32, 0), ChunkGrid::chunk_index_to_coords(1024));
-        assert_eq!((32, 32, 0), ChunkGrid::chunk_index_to_coords(1025));
+    fn palette_memory() {
+        // the test scene main.rs used to build: seven chunks full of logs and one of air
+        let mut grid = ChunkGrid::new();
+        let log = grid.registry.by_name("log").unwrap();
+        for pos in [0, 1].into_iter().flat_map(|a| {
+            [0, 1]
+                .into_iter()
+                .flat_map(move |b| [0, 1].map(|c| ChunkPos::new(a, b, c)))
+        }) {
+            let mut chunk = Chunk::new(pos);
+            if pos != ChunkPos::new(0, 1, 0) {
+                for local in LocalPos::all() {
+                    chunk.set_block(log, local);
+                }
+            }
+            grid.set_chunk(chunk);
+        }
+        // what the blocks took as a Box<[Option<Block>; CHUNK_VOLUME]> per chunk
+        let unpacked = CHUNK_VOLUME * size_of::<Option<Block>>();
+        let packed: usize = grid.chunks.values().map(|c| c.blocks.heap_size()).sum();
+        assert_eq!(8, grid.chunks.len());
+        assert!(grid.chunks.values().all(|c| c.blocks.single().is_some()));
+        assert!(packed < 100 && packed * 1000 < 8 * unpacked);
+
+        // a second kind of block costs a bit per block
+        grid.set_block(WorldPos::new(3, 3, 3), Block::new(1));
+        let chunk = grid.get_chunk_from_coords(ChunkPos::new(0, 0, 0)).unwrap();
+        assert_eq!(Some(Block::new(1)), chunk.get_block(LocalPos::new(3, 3, 3)));
+        assert_eq!(Some(log), chunk.get_block(LocalPos::new(3, 3, 4)));
+        assert!(chunk.blocks.heap_size() < CHUNK_VOLUME / 8 + 100);
+        assert!(chunk.blocks.heap_size() * 20 < unpacked);
     }
 }
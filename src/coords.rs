@@ -0,0 +1,326 @@
+use std::ops::{Add, Sub};
+
+use bevy::prelude::*;
+
+/// Blocks along one edge of a chunk.
+pub const CHUNK_WIDTH: i32 = 32;
+/// Blocks in a chunk.
+pub const CHUNK_VOLUME: usize = (CHUNK_WIDTH * CHUNK_WIDTH * CHUNK_WIDTH) as usize;
+
+/// The six faces of a block, in the order the mesher lays out its vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    /// "top" in the vertex tables
+    PosZ,
+    /// "bottom" in the vertex tables
+    NegZ,
+    /// "right" in the vertex tables
+    PosX,
+    /// "left" in the vertex tables
+    NegX,
+    /// "front" in the vertex tables
+    PosY,
+    /// "back" in the vertex tables
+    NegY,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::PosZ,
+        Face::NegZ,
+        Face::PosX,
+        Face::NegX,
+        Face::PosY,
+        Face::NegY,
+    ];
+
+    pub fn offset(self) -> IVec3 {
+        match self {
+            Face::PosZ => IVec3::Z,
+            Face::NegZ => -IVec3::Z,
+            Face::PosX => IVec3::X,
+            Face::NegX => -IVec3::X,
+            Face::PosY => IVec3::Y,
+            Face::NegY => -IVec3::Y,
+        }
+    }
+    pub fn opposite(self) -> Face {
+        match self {
+            Face::PosZ => Face::NegZ,
+            Face::NegZ => Face::PosZ,
+            Face::PosX => Face::NegX,
+            Face::NegX => Face::PosX,
+            Face::PosY => Face::NegY,
+            Face::NegY => Face::PosY,
+        }
+    }
+    /// Position of this face in [`Face::ALL`] and in per-face arrays.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Absolute block coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct WorldPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl WorldPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+    /// The chunk this block is in and its position inside that chunk.
+    pub fn split(self) -> (ChunkPos, LocalPos) {
+        (self.into(), self.into())
+    }
+    pub fn neighbour(self, face: Face) -> WorldPos {
+        self + face.offset()
+    }
+    pub fn neighbours(self) -> impl Iterator<Item = (Face, WorldPos)> {
+        Face::ALL.into_iter().map(move |f| (f, self.neighbour(f)))
+    }
+    pub fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.z)
+    }
+    pub fn as_vec3(self) -> Vec3 {
+        self.as_ivec3().as_vec3()
+    }
+}
+
+/// Coordinates of a chunk in chunk units; chunk `(1, 0, 0)` starts at block `(32, 0, 0)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+    /// World position of a block inside this chunk.
+    pub fn world(self, local: LocalPos) -> WorldPos {
+        WorldPos::from(self) + local.as_ivec3()
+    }
+    pub fn neighbour(self, face: Face) -> ChunkPos {
+        self + face.offset()
+    }
+    pub fn neighbours(self) -> impl Iterator<Item = (Face, ChunkPos)> {
+        Face::ALL.into_iter().map(move |f| (f, self.neighbour(f)))
+    }
+    /// The 26 chunks sharing a face, an edge or a corner with this one.
+    pub fn surrounding(self) -> impl Iterator<Item = ChunkPos> {
+        (-1..=1)
+            .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+            .filter(|offset| *offset != IVec3::ZERO)
+            .map(move |offset| self + offset)
+    }
+    pub fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.z)
+    }
+}
+
+/// Block coordinates inside a chunk, always within `0..CHUNK_WIDTH` on every axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LocalPos {
+    x: u8,
+    y: u8,
+    z: u8,
+}
+
+impl LocalPos {
+    /// Panics if a coordinate is outside the chunk.
+    pub fn new(x: usize, y: usize, z: usize) -> Self {
+        Self::try_new(x as i32, y as i32, z as i32)
+            .unwrap_or_else(|| panic!("local position out of chunk: {} {} {}", x, y, z))
+    }
+    /// Returns `None` if a coordinate is outside the chunk.
+    pub fn try_new(x: i32, y: i32, z: i32) -> Option<Self> {
+        let range = 0..CHUNK_WIDTH;
+        if range.contains(&x) && range.contains(&y) && range.contains(&z) {
+            Some(Self {
+                x: x as u8,
+                y: y as u8,
+                z: z as u8,
+            })
+        } else {
+            None
+        }
+    }
+    pub fn x(self) -> usize {
+        self.x as usize
+    }
+    pub fn y(self) -> usize {
+        self.y as usize
+    }
+    pub fn z(self) -> usize {
+        self.z as usize
+    }
+    pub fn from_index(index: usize) -> Self {
+        let x = index % 32; // 0..32 then resets to 0
+        let y = index / (32 * 32); // 0..1 is equal to a 32 * 32 block area
+        let z = (index / 32) % 32; // 0..1 is equal to a 32 block area
+        Self::new(x, y, z)
+    }
+    pub fn index(self) -> usize {
+        let x = self.x(); // incremental is the same
+        let y = self.y() * 32 * 32; // incremental is 1:32*32
+        let z = self.z() * 32; // incremental is 1:32
+        x + y + z
+    }
+    /// Every position in a chunk, in index order.
+    pub fn all() -> impl Iterator<Item = LocalPos> {
+        (0..CHUNK_VOLUME).map(Self::from_index)
+    }
+    /// The neighbouring position, or `None` if it lies in another chunk.
+    pub fn neighbour(self, face: Face) -> Option<LocalPos> {
+        let p = self.as_ivec3() + face.offset();
+        Self::try_new(p.x, p.y, p.z)
+    }
+    pub fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x as i32, self.y as i32, self.z as i32)
+    }
+}
+
+impl From<WorldPos> for ChunkPos {
+    fn from(pos: WorldPos) -> Self {
+        Self::new(
+            pos.x.div_euclid(CHUNK_WIDTH),
+            pos.y.div_euclid(CHUNK_WIDTH),
+            pos.z.div_euclid(CHUNK_WIDTH),
+        )
+    }
+}
+
+impl From<WorldPos> for LocalPos {
+    fn from(pos: WorldPos) -> Self {
+        Self {
+            x: pos.x.rem_euclid(CHUNK_WIDTH) as u8,
+            y: pos.y.rem_euclid(CHUNK_WIDTH) as u8,
+            z: pos.z.rem_euclid(CHUNK_WIDTH) as u8,
+        }
+    }
+}
+
+/// The first block of the chunk.
+impl From<ChunkPos> for WorldPos {
+    fn from(pos: ChunkPos) -> Self {
+        Self::new(
+            pos.x * CHUNK_WIDTH,
+            pos.y * CHUNK_WIDTH,
+            pos.z * CHUNK_WIDTH,
+        )
+    }
+}
+
+impl From<IVec3> for WorldPos {
+    fn from(v: IVec3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<IVec3> for ChunkPos {
+    fn from(v: IVec3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+impl Add<IVec3> for WorldPos {
+    type Output = WorldPos;
+
+    fn add(self, rhs: IVec3) -> Self::Output {
+        (self.as_ivec3() + rhs).into()
+    }
+}
+
+impl Sub<IVec3> for WorldPos {
+    type Output = WorldPos;
+
+    fn sub(self, rhs: IVec3) -> Self::Output {
+        (self.as_ivec3() - rhs).into()
+    }
+}
+
+impl Sub for WorldPos {
+    type Output = IVec3;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.as_ivec3() - rhs.as_ivec3()
+    }
+}
+
+impl Add<IVec3> for ChunkPos {
+    type Output = ChunkPos;
+
+    fn add(self, rhs: IVec3) -> Self::Output {
+        (self.as_ivec3() + rhs).into()
+    }
+}
+
+impl Sub<IVec3> for ChunkPos {
+    type Output = ChunkPos;
+
+    fn sub(self, rhs: IVec3) -> Self::Output {
+        (self.as_ivec3() - rhs).into()
+    }
+}
+
+impl Sub for ChunkPos {
+    type Output = IVec3;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.as_ivec3() - rhs.as_ivec3()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_to_chunk_and_local() {
+        assert_eq!(
+            (ChunkPos::new(0, 0, 0), LocalPos::new(1, 2, 3)),
+            WorldPos::new(1, 2, 3).split()
+        );
+        assert_eq!(
+            (ChunkPos::new(1, -1, 0), LocalPos::new(0, 31, 31)),
+            WorldPos::new(32, -1, 31).split()
+        );
+        assert_eq!(
+            (ChunkPos::new(-2, -1, -1), LocalPos::new(31, 0, 0)),
+            WorldPos::new(-33, -32, -32).split()
+        );
+
+        for pos in [WorldPos::new(-33, 70, -1), WorldPos::new(0, -64, 95)] {
+            let (chunk, local) = pos.split();
+            assert_eq!(pos, chunk.world(local));
+        }
+    }
+
+    #[test]
+    fn local_neighbours() {
+        let corner = LocalPos::new(31, 0, 0);
+        assert_eq!(None, corner.neighbour(Face::PosX));
+        assert_eq!(None, corner.neighbour(Face::NegY));
+        assert_eq!(None, corner.neighbour(Face::NegZ));
+        assert_eq!(Some(LocalPos::new(30, 0, 0)), corner.neighbour(Face::NegX));
+        assert_eq!(Some(LocalPos::new(31, 1, 0)), corner.neighbour(Face::PosY));
+        assert_eq!(Some(LocalPos::new(31, 0, 1)), corner.neighbour(Face::PosZ));
+
+        for face in Face::ALL {
+            let pos = WorldPos::new(5, 5, 5);
+            assert_eq!(pos, pos.neighbour(face).neighbour(face.opposite()));
+        }
+        assert_eq!(6, ChunkPos::default().neighbours().count());
+
+        let surrounding: Vec<_> = ChunkPos::new(1, 1, 1).surrounding().collect();
+        assert_eq!(26, surrounding.len());
+        assert!(!surrounding.contains(&ChunkPos::new(1, 1, 1)));
+        assert!(surrounding.contains(&ChunkPos::new(0, 2, 2)));
+    }
+}
@@ -0,0 +1,340 @@
+use std::ops::Range;
+
+use bevy::math::IVec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::biome::Biome;
+use crate::block::Block;
+use crate::chunk::Chunk;
+use crate::coords::{ChunkPos, LocalPos, WorldPos, CHUNK_WIDTH};
+
+/// The top of a column of generated terrain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    /// Height of the topmost ground block.
+    pub y: i32,
+    pub block: Block,
+    pub biome: Biome,
+}
+
+/// What decoration needs to know about the shaped terrain, also outside the chunk being
+/// decorated since features reach across chunk borders.
+pub trait Terrain {
+    /// The top of the column at `x`, `z`, unless a cave opens up there.
+    fn surface(&self, x: i32, z: i32) -> Option<Surface>;
+    fn biome_at(&self, x: i32, z: i32) -> Biome;
+}
+
+/// What a [`Feature`] places.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeatureKind {
+    /// A blob of about `size` blocks wandering through `replaces`, like ore in stone.
+    Vein {
+        block: Block,
+        size: u32,
+        replaces: Block,
+    },
+    /// A lump of `block` up to `radius` blocks across, half sunk into the ground.
+    Boulder { block: Block, radius: f32 },
+    /// A trunk of `log` `height` blocks tall, topped with `leaves`.
+    Tree {
+        log: Block,
+        leaves: Block,
+        height: Range<i32>,
+    },
+    /// A single block on top of the ground, like a flower.
+    Plant { block: Block },
+}
+
+/// Something the decoration stage places in the terrain, and where and how often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feature {
+    pub kind: FeatureKind,
+    /// Tries per chunk. A fraction is the chance of one more try, so features can be rarer
+    /// than one per chunk.
+    pub per_chunk: f32,
+    /// Heights the feature starts at: anywhere in them for veins, the ground for the rest.
+    pub heights: Range<i32>,
+    /// Biomes the feature shows up in, every one when empty.
+    pub biomes: Vec<Biome>,
+    /// Ground block surface features have to stand on, any when `None`.
+    pub on: Option<Block>,
+}
+
+impl Feature {
+    pub fn new(kind: FeatureKind, per_chunk: f32) -> Self {
+        Self {
+            kind,
+            per_chunk,
+            heights: i32::MIN..i32::MAX,
+            biomes: vec![],
+            on: None,
+        }
+    }
+    pub fn heights(mut self, heights: Range<i32>) -> Self {
+        self.heights = heights;
+        self
+    }
+    pub fn biomes(mut self, biomes: &[Biome]) -> Self {
+        self.biomes = biomes.to_vec();
+        self
+    }
+    pub fn on(mut self, block: Block) -> Self {
+        self.on = Some(block);
+        self
+    }
+    /// Whether the feature grows out of the ground rather than anywhere underground.
+    fn on_surface(&self) -> bool {
+        !matches!(self.kind, FeatureKind::Vein { .. })
+    }
+    fn allows(&self, biome: Biome) -> bool {
+        self.biomes.is_empty() || self.biomes.contains(&biome)
+    }
+    /// Places the parts of the feature starting at `origin` that fall inside `chunk`.
+    fn place(&self, chunk: &mut Chunk, origin: WorldPos, rng: &mut StdRng) {
+        match &self.kind {
+            FeatureKind::Vein {
+                block,
+                size,
+                replaces,
+            } => {
+                let mut pos = origin;
+                for _ in 0..*size {
+                    put(chunk, pos, *block, |b| b == Some(*replaces));
+                    let step = rng.gen_range(0..6);
+                    pos.x += [1, -1, 0, 0, 0, 0][step];
+                    pos.y += [0, 0, 1, -1, 0, 0][step];
+                    pos.z += [0, 0, 0, 0, 1, -1][step];
+                }
+            }
+            FeatureKind::Boulder { block, radius } => {
+                let radius = rng.gen_range(1.0..=*radius);
+                let r = radius.ceil() as i32;
+                for x in -r..=r {
+                    for y in -r..=r {
+                        for z in -r..=r {
+                            if ((x * x + y * y + z * z) as f32) <= radius * radius {
+                                let pos = origin + IVec3::new(x, y, z);
+                                put(chunk, pos, *block, |b| b.is_none());
+                            }
+                        }
+                    }
+                }
+            }
+            FeatureKind::Tree {
+                log,
+                leaves,
+                height,
+            } => {
+                let height = rng.gen_range(height.clone());
+                let top = origin.y + height;
+                // two wide layers around the top of the trunk, two narrow ones above
+                for y in top - 2..=top + 1 {
+                    let r: i32 = if y < top { 2 } else { 1 };
+                    for x in -r..=r {
+                        for z in -r..=r {
+                            // corners are ragged
+                            if x.abs() == r && z.abs() == r && (y > top || rng.gen_bool(0.5)) {
+                                continue;
+                            }
+                            let pos = WorldPos::new(origin.x + x, y, origin.z + z);
+                            put(chunk, pos, *leaves, |b| b.is_none());
+                        }
+                    }
+                }
+                for y in origin.y + 1..=top {
+                    let pos = WorldPos::new(origin.x, y, origin.z);
+                    put(chunk, pos, *log, |b| b.is_none() || b == Some(*leaves));
+                }
+            }
+            FeatureKind::Plant { block } => {
+                put(chunk, origin + IVec3::Y, *block, |b| b.is_none());
+            }
+        }
+    }
+}
+
+/// Puts `block` at `pos` if it's inside `chunk` and `replace` accepts what's there.
+fn put(chunk: &mut Chunk, pos: WorldPos, block: Block, replace: impl Fn(Option<Block>) -> bool) {
+    let (chunk_pos, local) = pos.split();
+    if chunk_pos == chunk.pos && replace(chunk.get_block(local)) {
+        chunk.replace_block(local, Some(block));
+    }
+}
+
+/// The random numbers a feature draws in the chunk at `pos`, the same every time for the
+/// same world seed.
+pub fn chunk_rng(seed: u32, pos: ChunkPos, feature: usize) -> StdRng {
+    let mut hash = (seed as u64) << 32 | feature as u64;
+    for v in [pos.x, pos.y, pos.z] {
+        hash = (hash ^ v as u32 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        hash ^= hash >> 31;
+    }
+    StdRng::seed_from_u64(hash)
+}
+
+/// Places `features` in the already shaped `chunk`, in order. Every chunk places its own
+/// features, and since they reach across chunk borders a chunk also gets the parts of its
+/// neighbours' features that stick into it. Underground features are placed by the chunk
+/// they start in, surface features by the column of chunks they stand on.
+pub fn decorate(chunk: &mut Chunk, seed: u32, features: &[Feature], terrain: &impl Terrain) {
+    let bottom = WorldPos::from(chunk.pos).y;
+    for (i, feature) in features.iter().enumerate() {
+        let owners: Vec<ChunkPos> = if feature.on_surface() {
+            (-1..=1)
+                .flat_map(|z| (-1..=1).map(move |x| IVec3::new(x, 0, z)))
+                .map(|offset| ChunkPos::new(chunk.pos.x, 0, chunk.pos.z) + offset)
+                .collect()
+        } else {
+            std::iter::once(chunk.pos)
+                .chain(chunk.pos.surrounding())
+                .filter(|owner| {
+                    let y = WorldPos::from(*owner).y;
+                    y < feature.heights.end && y + CHUNK_WIDTH > feature.heights.start
+                })
+                .collect()
+        };
+        for owner in owners {
+            let mut rng = chunk_rng(seed, owner, i);
+            let fraction = feature.per_chunk.fract();
+            let tries = feature.per_chunk as u32 + rng.gen_bool(fraction as f64) as u32;
+            for _ in 0..tries {
+                // each try draws from its own generator, so skipping one doesn't
+                // change where the others go
+                let mut rng = StdRng::seed_from_u64(rng.gen());
+                let local = LocalPos::new(
+                    rng.gen_range(0..CHUNK_WIDTH as usize),
+                    rng.gen_range(0..CHUNK_WIDTH as usize),
+                    rng.gen_range(0..CHUNK_WIDTH as usize),
+                );
+                let mut origin = owner.world(local);
+                if feature.on_surface() {
+                    let surface = match terrain.surface(origin.x, origin.z) {
+                        Some(surface) => surface,
+                        None => continue,
+                    };
+                    // too far below or above to reach into the chunk
+                    if surface.y < bottom - CHUNK_WIDTH
+                        || surface.y > bottom + CHUNK_WIDTH * 2
+                        || !feature.allows(surface.biome)
+                        || matches!(feature.on, Some(on) if on != surface.block)
+                    {
+                        continue;
+                    }
+                    origin.y = surface.y;
+                } else if !feature.biomes.is_empty()
+                    && !feature.allows(terrain.biome_at(origin.x, origin.z))
+                {
+                    continue;
+                }
+                if feature.heights.contains(&origin.y) {
+                    feature.place(chunk, origin, &mut rng);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRASS: Block = Block::new(0);
+    const LOG: Block = Block::new(20);
+    const LEAVES: Block = Block::new(52);
+
+    /// Grass up to height 10 everywhere.
+    struct Flat(Biome);
+
+    impl Terrain for Flat {
+        fn surface(&self, _x: i32, _z: i32) -> Option<Surface> {
+            Some(Surface {
+                y: 10,
+                block: GRASS,
+                biome: self.0,
+            })
+        }
+        fn biome_at(&self, _x: i32, _z: i32) -> Biome {
+            self.0
+        }
+    }
+
+    fn forest() -> Vec<Feature> {
+        let tree = FeatureKind::Tree {
+            log: LOG,
+            leaves: LEAVES,
+            height: 4..7,
+        };
+        vec![Feature::new(tree, 6.).biomes(&[Biome::Forest]).on(GRASS)]
+    }
+
+    fn decorated(pos: ChunkPos, terrain: &Flat) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        for local in LocalPos::all().filter(|l| pos.world(*l).y <= 10) {
+            chunk.set_block(GRASS, local);
+        }
+        decorate(&mut chunk, 9, &forest(), terrain);
+        chunk
+    }
+
+    #[test]
+    fn chunk_rngs_are_seeded() {
+        let numbers = |seed, pos, feature| {
+            let mut rng = chunk_rng(seed, pos, feature);
+            [(); 4].map(|_| rng.gen::<u32>())
+        };
+        let pos = ChunkPos::new(3, -1, 7);
+        assert_eq!(numbers(1, pos, 0), numbers(1, pos, 0));
+        assert_ne!(numbers(1, pos, 0), numbers(2, pos, 0));
+        assert_ne!(numbers(1, pos, 0), numbers(1, pos, 1));
+        assert_ne!(numbers(1, pos, 0), numbers(1, ChunkPos::new(3, -1, 8), 0));
+    }
+
+    #[test]
+    fn trees_reach_across_chunks() {
+        let terrain = Flat(Biome::Forest);
+        let chunks: Vec<_> = (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |z| ChunkPos::new(x, 0, z)))
+            .map(|pos| decorated(pos, &terrain))
+            .collect();
+        let block = |pos: WorldPos| {
+            let (chunk, local) = pos.split();
+            let chunk = chunks.iter().find(|c| c.pos == chunk)?;
+            chunk.get_block(local)
+        };
+
+        let (mut trees, mut crossing) = (0, 0);
+        let middle = &chunks[4];
+        for local in LocalPos::all() {
+            let pos = middle.pos.world(local);
+            if middle.get_block(local) != Some(LOG) || block(pos + IVec3::Y) == Some(LOG) {
+                continue;
+            }
+            // every trunk stands on the ground and is crowned with leaves, even where the
+            // crown lies in another chunk than the trunk
+            trees += 1;
+            assert_eq!(Some(GRASS), block(WorldPos::new(pos.x, 10, pos.z)));
+            assert_eq!(Some(LEAVES), block(pos + IVec3::Y));
+            for side in [IVec3::X, -IVec3::X, IVec3::Z, -IVec3::Z] {
+                assert!(matches!(block(pos + side), Some(LEAVES) | Some(LOG)));
+                crossing += (ChunkPos::from(pos + side * 2) != middle.pos) as usize;
+            }
+        }
+        assert!(trees > 2);
+        assert!(crossing > 0);
+
+        // decorating again gives the same chunk
+        let again = decorated(ChunkPos::new(0, 0, 0), &terrain);
+        assert_eq!(chunks[4], again);
+    }
+
+    #[test]
+    fn features_keep_to_their_biomes() {
+        let desert = decorated(ChunkPos::new(0, 0, 0), &Flat(Biome::Desert));
+        assert!(desert
+            .blocks
+            .iter()
+            .all(|b| b.is_none() || b == Some(GRASS)));
+    }
+}
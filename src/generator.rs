@@ -0,0 +1,464 @@
+use crate::biome::{Biome, Climate};
+use crate::block::{Block, BlockRegistry};
+use crate::cave::CaveCarver;
+use crate::chunk::Chunk;
+use crate::coords::{ChunkPos, LocalPos, WorldPos, CHUNK_WIDTH};
+use crate::decoration::{decorate, Feature, FeatureKind, Surface, Terrain};
+use crate::noise::Noise;
+use crate::palette::PalettedBlocks;
+
+/// Fills chunks with blocks. A generator returns the same chunk for the same position every
+/// time, whatever order chunks are asked for in, so they can be generated on any thread.
+pub trait WorldGenerator: Send + Sync {
+    fn generate(&self, pos: ChunkPos) -> Chunk;
+}
+
+/// Rolling hills from a noise heightmap shaped by the biomes: each biome's surface block on
+/// top, a few blocks of what lies under it below that and stone all the way down, with
+/// water filling everything below sea level. 3D noise roughens the ground around the
+/// heightmap into cliffs and overhangs, and caves are carved out of it afterwards. Last,
+/// the terrain is decorated with ores, boulders and plants.
+#[derive(Debug, Clone)]
+pub struct HeightmapGenerator {
+    noise: Noise,
+    overhang_noise: Noise,
+    climate: Climate,
+    /// Blocks across one hill, roughly.
+    pub scale: f64,
+    /// How far 3D noise may push the ground up or down, as a fraction of the biome's
+    /// amplitude, so plains stay smooth while mountains grow cliffs and overhangs.
+    pub overhang: f64,
+    /// Blocks across one overhang, roughly.
+    pub overhang_scale: f64,
+    /// Hollows caves out of the ground. Without one the ground is solid.
+    pub caves: Option<CaveCarver>,
+    /// Placed after the terrain is shaped, in order.
+    pub features: Vec<Feature>,
+    /// Blocks of subsurface between the surface and the stone.
+    pub dirt_depth: i32,
+    /// Height of the water's surface.
+    pub sea_level: i32,
+    /// Surface and subsurface block of each biome, indexed like [`Biome::ALL`].
+    pub biome_blocks: [(Block, Block); Biome::ALL.len()],
+    pub stone: Block,
+    pub water: Block,
+    /// Covers the ground under water and along the shore, whatever the biome.
+    pub sand: Block,
+}
+
+/// The height, roughness and biome of a column.
+#[derive(Debug, Clone, Copy)]
+struct Column {
+    height: i32,
+    /// Blocks the ground may be pushed up or down by around `height`.
+    overhang: f64,
+    biome: Biome,
+}
+
+impl HeightmapGenerator {
+    pub fn new(seed: u32, registry: &BlockRegistry) -> Self {
+        let block = |name| {
+            registry
+                .by_name(name)
+                .unwrap_or_else(|| panic!("{} is a built-in block", name))
+        };
+        let stone = block("stone");
+        let vein = |name, size| FeatureKind::Vein {
+            block: block(name),
+            size,
+            replaces: stone,
+        };
+        let tree = FeatureKind::Tree {
+            log: block("log"),
+            leaves: block("leaves"),
+            height: 4..7,
+        };
+        let sea_level = 24;
+        Self {
+            noise: Noise::new(seed),
+            overhang_noise: Noise::new(seed.wrapping_add(0x9e37_79b9)),
+            climate: Climate::new(seed),
+            scale: 96.,
+            overhang: 0.5,
+            overhang_scale: 16.,
+            caves: Some(CaveCarver::new(seed)),
+            features: vec![
+                Feature::new(vein("dirt", 32), 4.).heights(-64..56),
+                Feature::new(vein("gravel", 32), 3.).heights(-64..56),
+                Feature::new(vein("coal_ore", 16), 10.).heights(-128..96),
+                Feature::new(vein("iron_ore", 8), 8.).heights(-128..48),
+                Feature::new(vein("gold_ore", 8), 2.).heights(-128..16),
+                Feature::new(vein("diamond_ore", 6), 1.).heights(i32::MIN..-16),
+                Feature::new(
+                    FeatureKind::Boulder {
+                        block: block("cobblestone"),
+                        radius: 2.5,
+                    },
+                    0.5,
+                )
+                .heights(sea_level + 1..i32::MAX)
+                .biomes(&[Biome::Plains, Biome::Tundra, Biome::Mountains]),
+                Feature::new(tree.clone(), 10.)
+                    .biomes(&[Biome::Forest])
+                    .on(block("grass")),
+                Feature::new(tree, 0.5)
+                    .biomes(&[Biome::Plains])
+                    .on(block("grass")),
+                Feature::new(
+                    FeatureKind::Plant {
+                        block: block("rose"),
+                    },
+                    4.,
+                )
+                .biomes(&[Biome::Plains, Biome::Forest])
+                .on(block("grass")),
+            ],
+            dirt_depth: 3,
+            sea_level,
+            biome_blocks: Biome::ALL.map(|b| (block(b.surface()), block(b.subsurface()))),
+            stone,
+            water: block("water"),
+            sand: block("sand"),
+        }
+    }
+    pub fn seed(&self) -> u32 {
+        self.noise.seed()
+    }
+    /// The climate the biomes come from, for coloring the world the same way.
+    pub fn climate(&self) -> Climate {
+        self.climate
+    }
+    /// Height of the heightmap at `x`, `z`, which overhangs and caves then reshape.
+    pub fn height(&self, x: i32, z: i32) -> i32 {
+        self.column(x, z).height
+    }
+    /// Surface and subsurface block of `biome`.
+    pub fn blocks(&self, biome: Biome) -> (Block, Block) {
+        self.biome_blocks[biome as usize]
+    }
+    fn column(&self, x: i32, z: i32) -> Column {
+        let blend = self.climate.blend(x as f64, z as f64);
+        let terrain = blend.terrain();
+        let (nx, nz) = (x as f64 / self.scale, z as f64 / self.scale);
+        let height = terrain.base_height + self.noise.fbm2(nx, nz, 4) * terrain.amplitude;
+        Column {
+            height: height.round() as i32,
+            overhang: terrain.amplitude * self.overhang,
+            biome: blend.dominant(),
+        }
+    }
+    /// Surface and subsurface block of `column`.
+    fn layers(&self, column: Column) -> (Block, Block) {
+        match self.blocks(column.biome) {
+            // beaches and sea floors
+            _ if column.height <= self.sea_level => (self.sand, self.sand),
+            blocks => blocks,
+        }
+    }
+    /// Whether the block at `x`, `y`, `z` in `column` is ground rather than air or water.
+    fn is_ground(&self, x: i32, y: i32, z: i32, column: Column) -> bool {
+        let depth = (column.height - y) as f64;
+        if depth.abs() >= column.overhang {
+            return depth >= 0.;
+        }
+        let s = self.overhang_scale;
+        let density = self
+            .overhang_noise
+            .fbm3(x as f64 / s, y as f64 / s, z as f64 / s, 2);
+        depth + density * column.overhang >= 0.
+    }
+    /// Fills the column at `x`, `z` of `chunk` from the top down, putting the surface block
+    /// on top of every stretch of ground, even ones under an overhang.
+    fn fill_column(&self, chunk: &mut Chunk, x: usize, z: usize, column: Column) {
+        let origin = WorldPos::from(chunk.pos);
+        let (wx, wz) = (origin.x + x as i32, origin.z + z as i32);
+        let (surface, subsurface) = self.layers(column);
+        let top = column.height + column.overhang.ceil() as i32;
+        // blocks of ground above, none out in the open
+        let mut depth = None;
+        // water only fills what's open to the sky, not the space under overhangs
+        let mut sky = true;
+        for y in (origin.y..=top.max(origin.y + CHUNK_WIDTH - 1)).rev() {
+            let block = if self.is_ground(wx, y, wz, column) {
+                let d = depth.map_or(0, |d| d + 1);
+                depth = Some(d);
+                sky = false;
+                if d == 0 {
+                    Some(surface)
+                } else if d <= self.dirt_depth {
+                    Some(subsurface)
+                } else {
+                    Some(self.stone)
+                }
+            } else {
+                depth = None;
+                if sky && y <= self.sea_level {
+                    Some(self.water)
+                } else {
+                    None
+                }
+            };
+            match block {
+                Some(block) if y < origin.y + CHUNK_WIDTH => {
+                    chunk.set_block(block, LocalPos::new(x, (y - origin.y) as usize, z))
+                }
+                _ => {}
+            }
+        }
+    }
+    /// The highest block caves may carve out of `column`. Under water they stay below the
+    /// sea floor so it keeps holding the water up.
+    fn cave_ceiling(&self, column: Column) -> i32 {
+        let floor = column.height - column.overhang.ceil() as i32;
+        if floor <= self.sea_level {
+            floor - self.dirt_depth - 1
+        } else {
+            i32::MAX
+        }
+    }
+}
+
+impl HeightmapGenerator {
+    /// The chunk at `pos` before decoration.
+    fn shape(&self, pos: ChunkPos) -> Chunk {
+        let origin = WorldPos::from(pos);
+        let mut columns = [Column {
+            height: 0,
+            overhang: 0.,
+            biome: Biome::Ocean,
+        }; (CHUNK_WIDTH * CHUNK_WIDTH) as usize];
+        for (i, column) in columns.iter_mut().enumerate() {
+            let (x, z) = (i as i32 % CHUNK_WIDTH, i as i32 / CHUNK_WIDTH);
+            *column = self.column(origin.x + x, origin.z + z);
+        }
+        let reach = |c: &Column| c.overhang.ceil() as i32;
+        let lowest = columns.iter().map(|c| c.height - reach(c)).min().unwrap();
+        let highest = columns.iter().map(|c| c.height + reach(c)).max().unwrap();
+
+        let mut chunk = Chunk::new(pos);
+        // high up in the air or deep down in the stone the whole chunk is one block
+        if origin.y > highest.max(self.sea_level) {
+            return chunk;
+        }
+        if origin.y + CHUNK_WIDTH <= lowest - self.dirt_depth {
+            chunk.blocks = PalettedBlocks::filled(Some(self.stone));
+        } else {
+            for (i, column) in columns.iter().enumerate() {
+                let (x, z) = (i % CHUNK_WIDTH as usize, i / CHUNK_WIDTH as usize);
+                self.fill_column(&mut chunk, x, z, *column);
+            }
+        }
+        if let Some(caves) = &self.caves {
+            caves.carve(&mut chunk, &columns.map(|c| self.cave_ceiling(c)));
+        }
+        chunk
+    }
+}
+
+impl WorldGenerator for HeightmapGenerator {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        let mut chunk = self.shape(pos);
+        decorate(&mut chunk, self.seed(), &self.features, self);
+        chunk
+    }
+}
+
+impl Terrain for HeightmapGenerator {
+    fn surface(&self, x: i32, z: i32) -> Option<Surface> {
+        let column = self.column(x, z);
+        let reach = column.overhang.ceil() as i32;
+        let y = (column.height - reach..=column.height + reach)
+            .rev()
+            .find(|y| self.is_ground(x, *y, z, column))?;
+        if let Some(caves) = &self.caves {
+            if y <= self.cave_ceiling(column) && caves.is_cave(x, y, z) {
+                return None;
+            }
+        }
+        Some(Surface {
+            y,
+            block: self.layers(column).0,
+            biome: column.biome,
+        })
+    }
+    fn biome_at(&self, x: i32, z: i32) -> Biome {
+        self.climate.biome_at(x as f64, z as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(seed: u32) -> HeightmapGenerator {
+        HeightmapGenerator::new(seed, &BlockRegistry::default())
+    }
+
+    #[test]
+    fn same_seed_same_chunks() {
+        let positions = [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(0, 1, 0),
+            ChunkPos::new(-3, 0, 7),
+            ChunkPos::new(12, -1, -5),
+        ];
+        let (a, b) = (generator(42), generator(42));
+        for pos in positions {
+            assert_eq!(a.generate(pos), b.generate(pos));
+        }
+        // asking in another order doesn't change anything
+        for pos in positions.iter().rev() {
+            assert_eq!(a.generate(*pos), b.generate(*pos));
+        }
+        let other = generator(43);
+        assert_ne!(
+            a.generate(ChunkPos::new(0, 1, 0)),
+            other.generate(ChunkPos::new(0, 1, 0))
+        );
+    }
+
+    #[test]
+    fn surface_over_subsurface_over_stone() {
+        let mut generator = generator(7);
+        // just the heightmap
+        generator.overhang = 0.;
+        generator.caves = None;
+        generator.features.clear();
+        let chunks = [-1, 0, 1, 2, 3].map(|y| generator.generate(ChunkPos::new(1, y, -1)));
+        let block = |local: LocalPos, y: i32| {
+            let chunk = &chunks[(y.div_euclid(CHUNK_WIDTH) + 1) as usize];
+            let local = LocalPos::new(local.x(), y.rem_euclid(CHUNK_WIDTH) as usize, local.z());
+            chunk.get_block(local)
+        };
+        for (x, z) in [(0, 0), (5, 17), (31, 31)] {
+            let (wx, wz) = (CHUNK_WIDTH + x as i32, -CHUNK_WIDTH + z as i32);
+            let height = generator.height(wx, wz);
+            // the chunks have room for the whole column
+            assert!((-28..127).contains(&height));
+            let biome = generator.climate().biome_at(wx as f64, wz as f64);
+            let (surface, subsurface) = match height <= generator.sea_level {
+                true => (generator.sand, generator.sand),
+                false => generator.blocks(biome),
+            };
+            let column = LocalPos::new(x, 0, z);
+            assert_eq!(Some(surface), block(column, height));
+            for y in height - 3..height {
+                assert_eq!(Some(subsurface), block(column, y));
+            }
+            assert_eq!(Some(generator.stone), block(column, height - 4));
+            assert_eq!(Some(generator.stone), block(column, -32));
+            for y in height + 1..=generator.sea_level {
+                assert_eq!(Some(generator.water), block(column, y));
+            }
+            assert_eq!(None, block(column, height.max(generator.sea_level) + 1));
+        }
+
+        // far above and below the hills
+        let sky = generator.generate(ChunkPos::new(0, 5, 0));
+        assert_eq!(Some(None), sky.blocks.single());
+        let deep = generator.generate(ChunkPos::new(0, -3, 0));
+        assert_eq!(Some(Some(generator.stone)), deep.blocks.single());
+    }
+
+    #[test]
+    fn biomes_pick_surface_blocks() {
+        let generator = generator(1);
+        let registry = BlockRegistry::default();
+        let mut surfaces = vec![];
+        for i in 0..400 {
+            let (x, z) = (i * 97, i * -61);
+            let surface = generator.layers(generator.column(x, z)).0;
+            let name = registry.get(surface).name.clone();
+            if !surfaces.contains(&name) {
+                surfaces.push(name);
+            }
+        }
+        surfaces.sort();
+        assert_eq!(vec!["grass", "sand", "snow", "stone"], surfaces);
+    }
+
+    #[test]
+    fn ground_hangs_over_air() {
+        let mut generator = generator(2);
+        generator.overhang = 2.;
+        generator.caves = None;
+        generator.features.clear();
+        let mut overhangs = 0;
+        for x in 0..4 {
+            let chunk = generator.generate(ChunkPos::new(x, 1, 0));
+            for local in LocalPos::all().filter(|l| l.y() > 0) {
+                let below = LocalPos::new(local.x(), local.y() - 1, local.z());
+                if chunk.is_block(local) && !chunk.is_block(below) {
+                    overhangs += 1;
+                }
+            }
+        }
+        assert!(overhangs > 0);
+    }
+
+    #[test]
+    fn caves_stay_under_the_sea_floor() {
+        let mut generator = generator(5);
+        generator.features.clear();
+        let mut solid = generator.clone();
+        solid.caves = None;
+        let mut carved = 0;
+        for pos in [
+            ChunkPos::new(0, -1, 0),
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(9, 0, 3),
+        ] {
+            let (caves, ground) = (generator.generate(pos), solid.generate(pos));
+            for local in LocalPos::all() {
+                if caves.get_block(local) == ground.get_block(local) {
+                    continue;
+                }
+                // caves only ever take blocks away, and only below the ceiling
+                assert_eq!(None, caves.get_block(local));
+                let world = pos.world(local);
+                let column = generator.column(world.x, world.z);
+                assert!(world.y <= generator.cave_ceiling(column));
+                carved += 1;
+            }
+        }
+        assert!(carved > 0);
+    }
+
+    #[test]
+    fn decoration_only_fills_in() {
+        let generator = generator(11);
+        let mut bare = generator.clone();
+        bare.features.clear();
+        let registry = BlockRegistry::default();
+        let name = |block: Option<Block>| block.map(|b| registry.get(b).name.as_str());
+        let mut placed = vec![];
+        let positions = (-2..=2).flat_map(|x| (-2..=1).map(move |y| ChunkPos::new(x, y, 3)));
+        for pos in positions {
+            let (decorated, bare) = (generator.generate(pos), bare.generate(pos));
+            for local in LocalPos::all() {
+                let (after, before) = (decorated.get_block(local), bare.get_block(local));
+                if after == before {
+                    continue;
+                }
+                // ores and veins take the place of stone, everything else fills air
+                match name(after).unwrap() {
+                    "dirt" | "gravel" | "coal_ore" | "iron_ore" | "gold_ore" | "diamond_ore" => {
+                        assert_eq!(Some("stone"), name(before))
+                    }
+                    _ => assert_eq!(None, before),
+                }
+                if !placed.contains(&name(after)) {
+                    placed.push(name(after));
+                }
+            }
+        }
+        for block in ["coal_ore", "iron_ore", "log", "leaves"] {
+            assert!(
+                placed.contains(&Some(block)),
+                "no {} in {:?}",
+                block,
+                placed
+            );
+        }
+    }
+}
@@ -1,3 +1,16 @@
+pub mod atlas;
+pub mod biome;
 pub mod block;
+pub mod cave;
 pub mod chunk;
+pub mod coords;
 pub mod debug;
+pub mod decoration;
+pub mod generator;
+pub mod material;
+pub mod mesh;
+pub mod noise;
+pub mod palette;
+pub mod shape;
+pub mod state;
+pub mod world;
@@ -5,25 +5,17 @@ use std::sync::Arc;
 use bevy::input::mouse::MouseMotion;
 use bevy::pbr::wireframe::{WireframeConfig, WireframePlugin};
 use bevy::prelude::*;
-use bevy::reflect::List;
 use bevy::render::options::WgpuOptions;
-use bevy::render::primitives::Plane;
 use bevy::render::render_resource::WgpuFeatures;
-use bevy::tasks::AsyncComputeTaskPool;
-use futures_lite::future;
-use lazy_static::lazy_static;
-use spin::mutex::Mutex;
 
-use bevy_craft_new::block::{Block, Texture};
-use bevy_craft_new::chunk::*;
+use bevy_craft_new::atlas::BlockAtlasPlugin;
+use bevy_craft_new::block::BlockRegistryPlugin;
 use bevy_craft_new::debug::DebugPlugin;
+use bevy_craft_new::generator::HeightmapGenerator;
+use bevy_craft_new::material::ChunkMaterialPlugin;
+use bevy_craft_new::mesh::Mesher;
+use bevy_craft_new::world::{ChunkViewer, MainWorld, VoxelWorld, VoxelWorldPlugin};
 
-lazy_static! {
-    static ref CHUNK_GRID: Mutex<ChunkGrid> = {
-        let c = ChunkGrid::default();
-        Mutex::new(c)
-    };
-}
 fn main() {
     App::new()
         .insert_resource(Msaa { samples: 4 })
@@ -38,19 +30,25 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_plugin(DebugPlugin)
         .add_plugin(WireframePlugin)
+        .insert_resource(WireframeConfig { global: true })
+        .add_plugin(ChunkMaterialPlugin)
+        .add_plugin(BlockRegistryPlugin)
+        .add_plugin(BlockAtlasPlugin)
+        .add_plugin(VoxelWorldPlugin::<MainWorld>::default())
         .add_state(GameState::InGame)
+        .add_startup_system(watch_assets)
         .add_startup_system(setup_camera)
         .add_system_set(SystemSet::on_enter(GameState::InGame).with_system(hide_cursor))
         .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(show_cursor))
         .add_system_set(
             SystemSet::on_update(GameState::InGame)
                 .with_system(move_camera)
-                .with_system(rotate_camera),
+                .with_system(rotate_camera)
+                .with_system(edit_blocks),
         )
         .add_system(switch_menu)
-        .add_startup_system(temp_chunk_spawn)
-        .add_system(queue_chunks)
-        .add_system(spawn_chunks)
+        .add_system(switch_mesher)
+        .add_startup_system(generate_terrain)
         .run();
 }
 
@@ -85,82 +83,94 @@ fn switch_menu(keyboard_input: Res<Input<KeyCode>>, mut state: ResMut<State<Game
     });
 }
 
-fn temp_chunk_spawn() {
-    for a in 0..2 {
-        for c in 0..2 {
-            for b in 0..2 {
-                let mut chunk = Chunk::new(a, b, c);
-                for x in 0..32 {
-                    for y in 0..32 {
-                        for z in 0..32 {
-                            if !(a == 0 && b == 1 && c == 0) {
-                                chunk.set_block(Block::new(Texture::Log), x, y, z);
-                            }
-                        }
-                    }
-                }
-                CHUNK_GRID.lock().set_chunk(chunk);
-            }
-        }
+/// Remeshes every chunk with the other mesher, to compare vertex counts and frame times.
+fn switch_mesher(keyboard_input: Res<Input<KeyCode>>, mut world: ResMut<VoxelWorld>) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        world.mesher = match world.mesher {
+            Mesher::Naive => Mesher::Greedy,
+            Mesher::Greedy => Mesher::Naive,
+        };
+        info!("Switched to {:?} meshing", world.mesher);
+        world.mark_all_dirty();
     }
-    info!("Finished generating chunks")
 }
 
-fn setup_camera(mut commands: Commands) {
-    let mut camera = PerspectiveCameraBundle::new_3d();
-    camera.transform = Transform::from_xyz(50., 2., 50.);
-    commands.spawn_bundle(camera).insert(Camera::default());
+/// Rebuilds the block atlas and registry as soon as their files are edited.
+fn watch_assets(asset_server: Res<AssetServer>) {
+    asset_server
+        .watch_for_changes()
+        .expect("watching the assets folder for changes");
 }
 
-fn queue_chunks(thread_pool: Res<AsyncComputeTaskPool>) {
-    let mut chunks = CHUNK_GRID.lock().chunks.to_vec();
-    for (i, c) in chunks.iter_mut().enumerate() {
-        if let Some(c) = c {
-            if !c.spawned {
-                info!("Queueing chunk {}", i);
-                // let task = thread_pool.spawn(async move {
-                //    c
-                // });
-                CHUNK_GRID.lock().add_to_queue(c);
-                c.spawned = true;
-                CHUNK_GRID.lock().chunks[i] = Some(c.clone());
-            }
-        }
-    }
+/// Streams hills in around the camera as it flies around.
+fn generate_terrain(mut world: ResMut<VoxelWorld>) {
+    let generator = HeightmapGenerator::new(0, &world.registry);
+    // tint the world with the climate its biomes were picked from
+    world.climate = generator.climate();
+    world.generator = Some(Arc::new(generator));
 }
 
-fn spawn_chunks(
-    mut wireframe_config: ResMut<WireframeConfig>,
-    mut commands: Commands,
-    assets: Res<AssetServer>,
-    // mut chunk_tasks: Query<(Entity, &mut Task<Chunk>)>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+/// Blocks the number keys select, in order.
+const HOTBAR: [&str; 9] = [
+    "stone",
+    "dirt",
+    "plank",
+    "log",
+    "slab",
+    "cobblestone_stairs",
+    "glass",
+    "leaves",
+    "table",
+];
+const HOTBAR_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+/// How far away blocks can be broken and placed.
+const REACH: f32 = 8.;
+
+/// Breaks the block in the middle of the screen on left click and places the selected block
+/// against the clicked face on right click.
+fn edit_blocks(
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    mut selected: Local<usize>,
+    query: Query<&Transform, With<Camera>>,
+    mut world: ResMut<VoxelWorld>,
 ) {
-    wireframe_config.global = true;
-    let mut queued_chunks = CHUNK_GRID.lock().queued_chunks.to_vec();
-    queued_chunks.iter_mut().for_each(|mut task| {
-        if !task.spawned {
-            // if let Some(c) = future::block_on(future::poll_once(task)) {
-            let texture = assets.load("TEXTURE_UV_MAP.png");
-            let mut material = StandardMaterial::default();
-            // material.base_color = Color::hex("78AC30").unwrap();
-            material.base_color_texture = Some(texture.clone());
-            material.unlit = true;
-
-            let mesh = CHUNK_GRID.lock().generate_chunk_mesh(task);
-            commands.spawn_bundle(MaterialMeshBundle {
-                mesh: meshes.add(mesh),
-                material: materials.add(material),
-                ..Default::default()
-            });
-
-            info!("Done spawning chunk!");
-            // }
-            task.spawned = true;
+    if let Some(i) = HOTBAR_KEYS.iter().position(|k| keyboard.just_pressed(*k)) {
+        *selected = i;
+        info!("Selected {}", HOTBAR[i]);
+    }
+    let transform = query.single();
+    let look = transform.forward();
+    let hit = match world.raycast(transform.translation, look, REACH) {
+        Some(hit) => hit,
+        None => return,
+    };
+    if mouse.just_pressed(MouseButton::Left) {
+        world.remove_block(hit.0);
+    } else if mouse.just_pressed(MouseButton::Right) {
+        if let Some(block) = world.registry.by_name(HOTBAR[*selected]) {
+            world.place_block(hit.0, hit.1, block, look);
         }
-    });
-    CHUNK_GRID.lock().queued_chunks = queued_chunks;
+    }
+}
+
+fn setup_camera(mut commands: Commands) {
+    let mut camera = PerspectiveCameraBundle::new_3d();
+    camera.transform = Transform::from_xyz(0., 60., 0.);
+    commands
+        .spawn_bundle(camera)
+        .insert(Camera::default())
+        .insert(ChunkViewer);
 }
 
 #[derive(Component)]
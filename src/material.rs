@@ -0,0 +1,436 @@
+use std::num::NonZeroU32;
+
+use bevy::ecs::system::lifetimeless::SRes;
+use bevy::ecs::system::SystemParamItem;
+use bevy::pbr::{AlphaMode, MaterialPipeline, SpecializedMaterial};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets};
+use bevy::render::render_resource::std140::{AsStd140, Std140};
+use bevy::render::render_resource::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferDescriptor, BufferSize, BufferUsages, Extent3d, FilterMode,
+    ImageCopyTexture, ImageDataLayout, Origin3d, RenderPipelineDescriptor, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderStages, Texture, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexFormat,
+    VertexStepMode,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::{RenderApp, RenderStage};
+
+use crate::block::RenderClass;
+
+/// Registers [`ChunkMaterial`] and [`BlockTextures`] and creates the [`ChunkMaterials`].
+/// Add it once, no matter how many voxel worlds the app has.
+pub struct ChunkMaterialPlugin;
+
+impl Plugin for ChunkMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BlockTextures>()
+            .add_plugin(RenderAssetPlugin::<BlockTextures>::default())
+            .add_plugin(MaterialPlugin::<ChunkMaterial>::default())
+            .init_resource::<ChunkMaterials>();
+        app.sub_app_mut(RenderApp)
+            .init_resource::<ChunkTimeBuffer>()
+            .add_system_to_stage(RenderStage::Extract, extract_time)
+            .add_system_to_stage(RenderStage::Prepare, prepare_time);
+    }
+}
+
+/// What the chunk shader animates textures by.
+#[derive(Clone, Default, AsStd140)]
+struct ChunkTimeUniform {
+    /// Seconds since startup.
+    time: f32,
+}
+
+/// The [`ChunkTimeUniform`] all chunk materials share, rewritten every frame so animated
+/// textures run without remeshing.
+pub struct ChunkTimeBuffer {
+    buffer: Buffer,
+}
+
+impl FromWorld for ChunkTimeBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("chunk_time_buffer"),
+            size: ChunkTimeUniform::std140_size_static() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer }
+    }
+}
+
+struct ExtractedTime(f32);
+
+fn extract_time(mut commands: Commands, time: Res<Time>) {
+    commands.insert_resource(ExtractedTime(time.seconds_since_startup() as f32));
+}
+
+fn prepare_time(
+    time: Res<ExtractedTime>,
+    buffer: Res<ChunkTimeBuffer>,
+    render_queue: Res<RenderQueue>,
+) {
+    let uniform = ChunkTimeUniform { time: time.0 };
+    render_queue.write_buffer(&buffer.buffer, 0, uniform.as_std140().as_bytes());
+}
+
+/// Block textures as the layers of one texture array, each with its own mip chain, so
+/// neighbouring textures never bleed into each other however small they're drawn.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "66685244-272b-46bb-b2e7-6753e628d53f"]
+pub struct BlockTextures {
+    /// Pixels along the edge of a layer at full size.
+    pub size: u32,
+    pub layers: u32,
+    /// RGBA pixels of all layers, one entry per mip level starting at full size.
+    pub mips: Vec<Vec<u8>>,
+}
+
+impl BlockTextures {
+    /// Takes the sRGB pixels of `size` by `size` layers one after another and averages them
+    /// down to 1 pixel for the mip chain.
+    pub fn new(size: u32, data: Vec<u8>) -> Self {
+        let layers = data.len() as u32 / (size * size * 4);
+        let mut mips = vec![data];
+        let mut width = size;
+        while width > 1 {
+            let half = width / 2;
+            let last = mips.last().unwrap();
+            let mut next = Vec::with_capacity((half * half * 4 * layers) as usize);
+            for layer in 0..layers {
+                let pixel = |x: u32, y: u32, c: u32| {
+                    let (x, y) = (x.min(width - 1), y.min(width - 1));
+                    last[(((layer * width + y) * width + x) * 4 + c) as usize] as u32
+                };
+                for y in 0..half {
+                    for x in 0..half {
+                        for c in 0..4 {
+                            let (x, y) = (x * 2, y * 2);
+                            let sum = pixel(x, y, c)
+                                + pixel(x + 1, y, c)
+                                + pixel(x, y + 1, c)
+                                + pixel(x + 1, y + 1, c);
+                            next.push(((sum + 2) / 4) as u8);
+                        }
+                    }
+                }
+            }
+            mips.push(next);
+            width = half;
+        }
+        Self { size, layers, mips }
+    }
+    /// Pixels along the edge of a layer at mip `level`.
+    pub fn mip_size(&self, level: usize) -> u32 {
+        (self.size >> level).max(1)
+    }
+    /// RGBA of one pixel at mip `level`.
+    pub fn pixel(&self, level: usize, layer: u32, x: u32, y: u32) -> [u8; 4] {
+        let size = self.mip_size(level);
+        let i = (((layer * size + y) * size + x) * 4) as usize;
+        self.mips[level][i..i + 4].try_into().unwrap()
+    }
+}
+
+pub struct GpuBlockTextures {
+    _texture: Texture,
+    view: TextureView,
+}
+
+impl RenderAsset for BlockTextures {
+    type ExtractedAsset = BlockTextures;
+    type PreparedAsset = GpuBlockTextures;
+    type Param = (SRes<RenderDevice>, SRes<RenderQueue>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        textures: Self::ExtractedAsset,
+        (render_device, render_queue): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let size = |level: usize| Extent3d {
+            width: textures.mip_size(level),
+            height: textures.mip_size(level),
+            depth_or_array_layers: textures.layers,
+        };
+        let texture = render_device.create_texture(&TextureDescriptor {
+            label: Some("block_textures"),
+            size: size(0),
+            mip_level_count: textures.mips.len() as u32,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        for (level, data) in textures.mips.iter().enumerate() {
+            let width = textures.mip_size(level);
+            render_queue.write_texture(
+                ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                data,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(width * 4),
+                    rows_per_image: NonZeroU32::new(width),
+                },
+                size(level),
+            );
+        }
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        Ok(GpuBlockTextures {
+            _texture: texture,
+            view,
+        })
+    }
+}
+
+/// Samples a layer of the [`BlockTextures`] per vertex. UVs count blocks, so a quad
+/// covering several blocks repeats its texture instead of stretching it. Cutout blocks drop
+/// transparent pixels, translucent blocks and liquids are alpha blended.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "c5352822-08eb-4da3-8b88-3d0b22f31f30"]
+pub struct ChunkMaterial {
+    pub textures: Handle<BlockTextures>,
+    pub class: RenderClass,
+}
+
+impl ChunkMaterial {
+    /// Texture array layer a vertex samples.
+    pub const ATTRIBUTE_LAYER: &'static str = "Vertex_Layer";
+    /// Frame count and seconds per frame of a vertex's texture. Frames are the layers after
+    /// [`ChunkMaterial::ATTRIBUTE_LAYER`].
+    pub const ATTRIBUTE_ANIMATION: &'static str = "Vertex_Animation";
+}
+
+/// One [`ChunkMaterial`] per [`RenderClass`], shared by the chunks of every world so their
+/// meshes batch together.
+pub struct ChunkMaterials {
+    materials: [Handle<ChunkMaterial>; 4],
+}
+
+impl ChunkMaterials {
+    pub fn get(&self, class: RenderClass) -> Handle<ChunkMaterial> {
+        self.materials[class.index()].clone()
+    }
+    /// Points every material at `textures`, which redraws all chunks with them.
+    pub fn set_textures(
+        &self,
+        textures: &Handle<BlockTextures>,
+        materials: &mut Assets<ChunkMaterial>,
+    ) {
+        for handle in &self.materials {
+            if let Some(material) = materials.get_mut(handle) {
+                material.textures = textures.clone();
+            }
+        }
+    }
+}
+
+impl FromWorld for ChunkMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ChunkMaterial>>().unwrap();
+        Self {
+            materials: RenderClass::ALL.map(|class| {
+                materials.add(ChunkMaterial {
+                    textures: Handle::default(),
+                    class,
+                })
+            }),
+        }
+    }
+}
+
+pub struct GpuChunkMaterial {
+    bind_group: BindGroup,
+    class: RenderClass,
+    _sampler: Sampler,
+}
+
+impl RenderAsset for ChunkMaterial {
+    type ExtractedAsset = ChunkMaterial;
+    type PreparedAsset = GpuChunkMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<MaterialPipeline<Self>>,
+        SRes<RenderAssets<BlockTextures>>,
+        SRes<ChunkTimeBuffer>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, pipeline, gpu_textures, time): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let textures = match gpu_textures.get(&material.textures) {
+            Some(textures) => textures,
+            None => return Err(PrepareAssetError::RetryNextUpdate(material)),
+        };
+        // blocks are pixel art, never blur them, only blend between mip levels
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&textures.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: time.buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("chunk_material_bind_group"),
+            layout: &pipeline.material_layout,
+        });
+
+        Ok(GpuChunkMaterial {
+            bind_group,
+            class: material.class,
+            _sampler: sampler,
+        })
+    }
+}
+
+impl SpecializedMaterial for ChunkMaterial {
+    type Key = RenderClass;
+
+    fn key(material: &<Self as RenderAsset>::PreparedAsset) -> Self::Key {
+        material.class
+    }
+
+    fn specialize(key: Self::Key, descriptor: &mut RenderPipelineDescriptor) {
+        if key == RenderClass::Cutout {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("ALPHA_MASK".to_string());
+            }
+        }
+        // attributes are interleaved in name order: animation, color, layer, normal,
+        // position, uv
+        descriptor.vertex.buffers = vec![VertexBufferLayout {
+            array_stride: 60,
+            step_mode: VertexStepMode::Vertex,
+            attributes: vec![
+                // Position
+                VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 40,
+                    shader_location: 0,
+                },
+                // Normal
+                VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 28,
+                    shader_location: 1,
+                },
+                // Uv
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 52,
+                    shader_location: 2,
+                },
+                // Layer
+                VertexAttribute {
+                    format: VertexFormat::Uint32,
+                    offset: 24,
+                    shader_location: 3,
+                },
+                // Color
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 8,
+                    shader_location: 4,
+                },
+                // Animation
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 0,
+                    shader_location: 5,
+                },
+            ],
+        }];
+    }
+
+    fn alpha_mode(material: &<Self as RenderAsset>::PreparedAsset) -> AlphaMode {
+        match material.class {
+            RenderClass::Opaque => AlphaMode::Opaque,
+            RenderClass::Cutout => AlphaMode::Mask(0.5),
+            RenderClass::Translucent | RenderClass::Liquid => AlphaMode::Blend,
+        }
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            ChunkTimeUniform::std140_size_static() as u64
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("chunk_material_layout"),
+        })
+    }
+
+    fn vertex_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(asset_server.load("shaders/chunk.wgsl"))
+    }
+
+    fn fragment_shader(asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(asset_server.load("shaders/chunk.wgsl"))
+    }
+}
@@ -0,0 +1,717 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+
+use crate::biome::Climate;
+use crate::block::{Block, BlockDef, BlockRegistry, FaceTexture, RenderClass};
+use crate::chunk::Chunk;
+use crate::coords::{Face, LocalPos, WorldPos, CHUNK_WIDTH};
+use crate::material::ChunkMaterial;
+use crate::shape::Shape;
+use crate::state::BlockState;
+
+/// Vertex data of a chunk mesh, built off the main thread and turned into a [`Mesh`] once done.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Texture coordinates in blocks, so they run past 1 on merged quads.
+    pub uvs: Vec<[f32; 2]>,
+    /// Texture array layer each vertex samples, see [`ChunkMaterial::ATTRIBUTE_LAYER`].
+    pub tiles: Vec<u32>,
+    /// How each vertex's texture animates, see [`ChunkMaterial::ATTRIBUTE_ANIMATION`].
+    pub animations: Vec<[f32; 2]>,
+    /// Climate tint times ambient occlusion.
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+    /// Adds one face of a box of `size` blocks whose first block is centred on `pos`,
+    /// with an ambient occlusion level per corner. Tinted faces take their color from the
+    /// snapshot's climate at each corner.
+    fn push_face(
+        &mut self,
+        face: Face,
+        pos: Vec3,
+        size: Vec3,
+        texture: FaceTexture,
+        ao: [u8; 4],
+        snapshot: &ChunkSnapshot,
+    ) {
+        let (_, u, v) = face_axes(face);
+        let first = face.index() * 4;
+        let tile = texture.tile.0 as u32;
+
+        let offset = self.positions.len() as u32;
+        for (i, (position, normal)) in VERTICES[first..first + 4].iter().enumerate() {
+            let corner = Vec3::from(*position) + Vec3::splat(0.5);
+            let position = pos + corner * size - Vec3::splat(0.5);
+            self.positions.push(position.into());
+            self.normals.push(*normal);
+            // which corner of the tile this is, stretched over the quad
+            let [corner_u, corner_v] = FACE_UVS[first + i];
+            self.uvs.push(if texture.rotated {
+                [(1. - corner_v) * size[v], corner_u * size[u]]
+            } else {
+                [corner_u * size[u], corner_v * size[v]]
+            });
+            self.tiles.push(tile);
+            self.animations.push(texture.animation);
+            let [r, g, b] = texture.tint.map_or([1.; 3], |tint| {
+                snapshot
+                    .climate
+                    .color(tint, position.x as f64, position.z as f64)
+            });
+            let light = AO_CURVE[ao[i] as usize];
+            self.colors.push([r * light, g * light, b * light, 1.]);
+        }
+        // split the quad along its brighter diagonal, otherwise the occlusion of a single
+        // corner bleeds across the whole quad depending on its orientation
+        let flip = if ao[1] + ao[3] > ao[0] + ao[2] { 1 } else { 0 };
+        self.indices.extend(
+            INDICES[face.index() * 6..face.index() * 6 + 6]
+                .iter()
+                .map(|i| (i - first as u32 + flip) % 4 + offset),
+        );
+    }
+    /// Adds two planes crossing diagonally through the block centred on `pos`, seen from
+    /// both sides and textured like the block's sides.
+    fn push_cross(&mut self, pos: Vec3, block: &BlockDef, snapshot: &ChunkSnapshot) {
+        let tile = block.tile(Face::PosZ).0 as u32;
+        let animation = block.animation(Face::PosZ);
+        let [r, g, b] = block.tint(Face::PosZ).map_or([1.; 3], |tint| {
+            snapshot.climate.color(tint, pos.x as f64, pos.z as f64)
+        });
+        let diagonals = [
+            [Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, -0.5, 0.5)],
+            [Vec3::new(0.5, -0.5, -0.5), Vec3::new(-0.5, -0.5, 0.5)],
+        ];
+        for [start, end] in diagonals {
+            let corners = [start, end, end + Vec3::Y, start + Vec3::Y];
+            let normal = (corners[1] - corners[0])
+                .cross(corners[3] - corners[0])
+                .normalize();
+            // once for each side, wound the other way round for the back
+            for (normal, order) in [(normal, [0, 1, 2, 2, 3, 0]), (-normal, [0, 3, 2, 2, 1, 0])] {
+                let offset = self.positions.len() as u32;
+                for (corner, uv) in corners.iter().zip([[0., 1.], [1., 1.], [1., 0.], [0., 0.]]) {
+                    self.positions.push((pos + *corner).into());
+                    self.normals.push(normal.into());
+                    self.uvs.push(uv);
+                    self.tiles.push(tile);
+                    self.animations.push(animation);
+                    self.colors.push([r, g, b, 1.]);
+                }
+                self.indices.extend(order.map(|i| i + offset));
+            }
+        }
+    }
+}
+
+impl From<MeshData> for Mesh {
+    fn from(data: MeshData) -> Self {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, data.positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, data.normals);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, data.uvs);
+        mesh.set_attribute(ChunkMaterial::ATTRIBUTE_LAYER, data.tiles);
+        mesh.set_attribute(ChunkMaterial::ATTRIBUTE_ANIMATION, data.animations);
+        mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, data.colors);
+        mesh.set_indices(Some(Indices::U32(data.indices)));
+        mesh
+    }
+}
+
+/// Vertex data of one chunk, split by [`RenderClass`] so each part gets its own material.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMeshData {
+    pub layers: [MeshData; 4],
+}
+
+impl ChunkMeshData {
+    pub fn layer(&self, class: RenderClass) -> &MeshData {
+        &self.layers[class.index()]
+    }
+    pub fn layer_mut(&mut self, class: RenderClass) -> &mut MeshData {
+        &mut self.layers[class.index()]
+    }
+    pub fn vertex_count(&self) -> usize {
+        self.layers.iter().map(MeshData::vertex_count).sum()
+    }
+}
+
+/// How chunk meshes are built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mesher {
+    /// One quad per visible block face.
+    Naive,
+    /// Merges coplanar faces of the same block into larger quads.
+    #[default]
+    Greedy,
+}
+
+impl Mesher {
+    pub fn mesh(self, snapshot: &ChunkSnapshot) -> ChunkMeshData {
+        let time = Instant::now();
+        let data = match self {
+            Mesher::Naive => snapshot.generate_chunk_data(),
+            Mesher::Greedy => snapshot.generate_greedy_chunk_data(),
+        };
+        debug!(
+            "{:?} meshed {:?}: {} vertices in {}μs",
+            self,
+            snapshot.chunk.pos,
+            data.vertex_count(),
+            time.elapsed().as_micros()
+        );
+        data
+    }
+}
+
+/// The axis a face points along, and the axes its texture's u and v run along.
+fn face_axes(face: Face) -> (usize, usize, usize) {
+    match face {
+        Face::PosZ | Face::NegZ => (2, 0, 1),
+        Face::PosX | Face::NegX => (0, 2, 1),
+        Face::PosY | Face::NegY => (1, 0, 2),
+    }
+}
+
+/// Brightness of a vertex by how many of its three neighbours are open, see
+/// [`ChunkSnapshot::face_ao`].
+const AO_CURVE: [f32; 4] = [0.4, 0.6, 0.8, 1.0];
+
+/// An immutable copy of a chunk and the 26 chunks around it, enough to mesh the chunk
+/// without access to the [`ChunkGrid`](crate::chunk::ChunkGrid).
+#[derive(Debug, Clone)]
+pub struct ChunkSnapshot {
+    pub chunk: Arc<Chunk>,
+    /// Surrounding chunks by [`ChunkSnapshot::neighbour_index`], `None` where no chunk is
+    /// loaded. The middle entry is always `None`, that's [`ChunkSnapshot::chunk`].
+    pub neighbours: [Option<Arc<Chunk>>; 27],
+    pub climate: Climate,
+    pub registry: Arc<BlockRegistry>,
+}
+
+impl ChunkSnapshot {
+    /// Where the chunk at `offset` (each axis in `-1..=1`) is kept in `neighbours`.
+    pub fn neighbour_index(offset: IVec3) -> usize {
+        let i = offset + IVec3::ONE;
+        (i.x + i.y * 3 + i.z * 9) as usize
+    }
+    /// The block at `pos` and its state, relative to the first block of the chunk and at
+    /// most one block outside of it.
+    fn block_at(&self, pos: IVec3) -> Option<(&BlockDef, BlockState)> {
+        let offset = IVec3::new(
+            pos.x.div_euclid(CHUNK_WIDTH),
+            pos.y.div_euclid(CHUNK_WIDTH),
+            pos.z.div_euclid(CHUNK_WIDTH),
+        );
+        let local = pos - offset * CHUNK_WIDTH;
+        let local = LocalPos::new(local.x as usize, local.y as usize, local.z as usize);
+        let chunk = if offset == IVec3::ZERO {
+            Some(&self.chunk)
+        } else {
+            self.neighbours[Self::neighbour_index(offset)].as_ref()
+        };
+        chunk
+            .and_then(|c| c.get_block(local))
+            .map(|b| (self.registry.get(b), b.state))
+    }
+    /// Whether there's an opaque block at `pos`, see [`ChunkSnapshot::block_at`].
+    fn occludes_at(&self, pos: IVec3) -> bool {
+        self.block_at(pos).is_some_and(|(def, _)| def.occludes())
+    }
+    /// Whether the block next to `local` on `face` hides that face of `block`, looking into
+    /// the neighbouring chunk on the chunk border. Only neighbours whose shape fills the
+    /// touching face do.
+    fn is_hidden(&self, block: &BlockDef, local: LocalPos, face: Face) -> bool {
+        self.block_at(local.as_ivec3() + face.offset())
+            .is_some_and(|(n, state)| n.hides_face(state, face, block))
+    }
+    /// Ambient occlusion of each corner of a face, in vertex order: 3 if nothing is in the
+    /// way, down to 0 if both side blocks in front of the face are solid. Only the blocks
+    /// in the layer the face looks into count.
+    fn face_ao(&self, local: LocalPos, face: Face) -> [u8; 4] {
+        let (_, u, v) = face_axes(face);
+        let front = local.as_ivec3() + face.offset();
+        let first = face.index() * 4;
+        let mut ao = [0; 4];
+        for (i, (corner, _)) in VERTICES[first..first + 4].iter().enumerate() {
+            let mut side_u = IVec3::ZERO;
+            side_u[u] = corner[u].signum() as i32;
+            let mut side_v = IVec3::ZERO;
+            side_v[v] = corner[v].signum() as i32;
+
+            let side1 = self.occludes_at(front + side_u);
+            let side2 = self.occludes_at(front + side_v);
+            let corner = self.occludes_at(front + side_u + side_v);
+            ao[i] = if side1 && side2 {
+                0
+            } else {
+                3 - side1 as u8 - side2 as u8 - corner as u8
+            };
+        }
+        ao
+    }
+    /// Which faces of the block are exposed, in [`Face::ALL`] order.
+    /// Faces bordering a chunk that isn't loaded count as exposed, air has none.
+    pub fn get_faces(&self, local: LocalPos) -> [bool; 6] {
+        match self.chunk.get_block(local) {
+            Some(b) => {
+                let block = self.registry.get(b);
+                Face::ALL.map(|face| !self.is_hidden(block, local, face))
+            }
+            None => [false; 6],
+        }
+    }
+    /// Adds a block that isn't a full cube. Box faces on the block's boundary are culled
+    /// and shaded like cube faces, the ones inside it are always drawn.
+    fn push_shape(&self, data: &mut ChunkMeshData, local: LocalPos, block: Block) {
+        let def = self.registry.get(block);
+        let origin = self.chunk.pos.world(local).as_vec3();
+        let layer = data.layer_mut(def.render);
+        if def.shape == Shape::Cross {
+            layer.push_cross(origin, def, self);
+            return;
+        }
+        for [from, to] in def.shape.boxes(block.state) {
+            for face in Face::ALL {
+                let (n, _, _) = face_axes(face);
+                let boundary = if face.offset()[n] > 0 {
+                    to[n] >= 1.
+                } else {
+                    from[n] <= 0.
+                };
+                if boundary && self.is_hidden(def, local, face) {
+                    continue;
+                }
+                let ao = if boundary {
+                    self.face_ao(local, face)
+                } else {
+                    [3; 4]
+                };
+                layer.push_face(
+                    face,
+                    origin + from,
+                    to - from,
+                    def.face(face, block.state),
+                    ao,
+                    self,
+                );
+            }
+        }
+    }
+    pub fn generate_chunk_data(&self) -> ChunkMeshData {
+        let chunk = &self.chunk;
+        let mut data = ChunkMeshData::default();
+        chunk.blocks.iter().enumerate().for_each(|(i, b)| {
+            match b {
+                None => {}
+                Some(b) => {
+                    let def = self.registry.get(b);
+                    let local = LocalPos::from_index(i);
+                    if def.shape != Shape::Cube {
+                        self.push_shape(&mut data, local, b);
+                        return;
+                    }
+                    let faces: [bool; 6] = self.get_faces(local);
+                    let origin = chunk.pos.world(local).as_vec3();
+
+                    // hidden faces are skipped entirely
+                    for face in Face::ALL.into_iter().filter(|f| faces[f.index()]) {
+                        let ao = self.face_ao(local, face);
+                        data.layer_mut(def.render).push_face(
+                            face,
+                            origin,
+                            Vec3::ONE,
+                            def.face(face, b.state),
+                            ao,
+                            self,
+                        );
+                    }
+                }
+            }
+        });
+        data
+    }
+    /// Sweeps every slice of the chunk per face direction, merging runs of exposed faces
+    /// of the same block and occlusion first along u, then along v. Blocks of other shapes
+    /// than cubes are added one by one.
+    pub fn generate_greedy_chunk_data(&self) -> ChunkMeshData {
+        let mut data = ChunkMeshData::default();
+        let origin = WorldPos::from(self.chunk.pos).as_vec3();
+
+        for (i, block) in self.chunk.blocks.iter().enumerate() {
+            if let Some(block) = block {
+                let def = self.registry.get(block);
+                if def.shape != Shape::Cube {
+                    self.push_shape(&mut data, LocalPos::from_index(i), block);
+                }
+            }
+        }
+
+        for face in Face::ALL {
+            let (n, u, v) = face_axes(face);
+            for slice in 0..32 {
+                let mut mask: [Option<(Block, [u8; 4])>; 32 * 32] = [None; 32 * 32];
+                for j in 0..32 {
+                    for i in 0..32 {
+                        let mut p = [0; 3];
+                        p[n] = slice;
+                        p[u] = i;
+                        p[v] = j;
+                        let local = LocalPos::new(p[0], p[1], p[2]);
+                        if let Some(block) = self.chunk.get_block(local) {
+                            let def = self.registry.get(block);
+                            if def.shape == Shape::Cube && !self.is_hidden(def, local, face) {
+                                mask[i + j * 32] = Some((block, self.face_ao(local, face)));
+                            }
+                        }
+                    }
+                }
+
+                for j in 0..32 {
+                    let mut i = 0;
+                    while i < 32 {
+                        let block = match mask[i + j * 32] {
+                            Some(b) => b,
+                            None => {
+                                i += 1;
+                                continue;
+                            }
+                        };
+                        let mut w = 1;
+                        while i + w < 32 && mask[i + w + j * 32] == Some(block) {
+                            w += 1;
+                        }
+                        let mut h = 1;
+                        while j + h < 32
+                            && (i..i + w).all(|k| mask[k + (j + h) * 32] == Some(block))
+                        {
+                            h += 1;
+                        }
+                        for dj in 0..h {
+                            for di in 0..w {
+                                mask[i + di + (j + dj) * 32] = None;
+                            }
+                        }
+
+                        let mut pos = [0.; 3];
+                        pos[n] = slice as f32;
+                        pos[u] = i as f32;
+                        pos[v] = j as f32;
+                        let mut size = [1.; 3];
+                        size[u] = w as f32;
+                        size[v] = h as f32;
+                        let (block, ao) = block;
+                        let def = self.registry.get(block);
+                        data.layer_mut(def.render).push_face(
+                            face,
+                            origin + Vec3::from(pos),
+                            Vec3::from(size),
+                            def.face(face, block.state),
+                            ao,
+                            self,
+                        );
+                        i += w;
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+const INDICES: [u32; 36] = [
+    0, 1, 2, 2, 3, 0, // top
+    4, 5, 6, 6, 7, 4, // bottom
+    8, 9, 10, 10, 11, 8, // right
+    12, 13, 14, 14, 15, 12, // left
+    16, 17, 18, 18, 19, 16, // front
+    20, 21, 22, 22, 23, 20, // back
+];
+
+/// Which corner of the texture each vertex in [`VERTICES`] shows, as fractions of a tile.
+const FACE_UVS: [[f32; 2]; 24] = [
+    // top
+    [1., 1.],
+    [0., 1.],
+    [0., 0.],
+    [1., 0.],
+    // bottom
+    [0., 0.],
+    [1., 0.],
+    [1., 1.],
+    [0., 1.],
+    // right
+    [1., 1.],
+    [1., 0.],
+    [0., 0.],
+    [0., 1.],
+    // left
+    [1., 1.],
+    [1., 0.],
+    [0., 0.],
+    [0., 1.],
+    // front
+    [1., 0.],
+    [0., 0.],
+    [0., 1.],
+    [1., 1.],
+    // back
+    [0., 0.],
+    [1., 0.],
+    [1., 1.],
+    [0., 1.],
+];
+
+const VERTICES: &[([f32; 3], [f32; 3]); 24] = &[
+    // Top
+    ([-0.5, -0.5, 0.5], [0., 0., 1.0]),
+    ([0.5, -0.5, 0.5], [0., 0., 1.0]),
+    ([0.5, 0.5, 0.5], [0., 0., 1.0]),
+    ([-0.5, 0.5, 0.5], [0., 0., 1.0]),
+    // Bottom
+    ([-0.5, 0.5, -0.5], [0., 0., -1.0]),
+    ([0.5, 0.5, -0.5], [0., 0., -1.0]),
+    ([0.5, -0.5, -0.5], [0., 0., -1.0]),
+    ([-0.5, -0.5, -0.5], [0., 0., -1.0]),
+    // Right
+    ([0.5, -0.5, -0.5], [1.0, 0., 0.]),
+    ([0.5, 0.5, -0.5], [1.0, 0., 0.]),
+    ([0.5, 0.5, 0.5], [1.0, 0., 0.]),
+    ([0.5, -0.5, 0.5], [1.0, 0., 0.]),
+    // Left
+    ([-0.5, -0.5, 0.5], [-1.0, 0., 0.]),
+    ([-0.5, 0.5, 0.5], [-1.0, 0., 0.]),
+    ([-0.5, 0.5, -0.5], [-1.0, 0., 0.]),
+    ([-0.5, -0.5, -0.5], [-1.0, 0., 0.]),
+    // Front
+    ([0.5, 0.5, -0.5], [0., 1.0, 0.]),
+    ([-0.5, 0.5, -0.5], [0., 1.0, 0.]),
+    ([-0.5, 0.5, 0.5], [0., 1.0, 0.]),
+    ([0.5, 0.5, 0.5], [0., 1.0, 0.]),
+    // Back
+    ([0.5, -0.5, 0.5], [0., -1.0, 0.]),
+    ([-0.5, -0.5, 0.5], [0., -1.0, 0.]),
+    ([-0.5, -0.5, -0.5], [0., -1.0, 0.]),
+    ([0.5, -0.5, -0.5], [0., -1.0, 0.]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::{AtlasLayout, Tile};
+    use crate::biome::Tint;
+    use crate::chunk::ChunkGrid;
+    use crate::coords::{ChunkPos, WorldPos};
+
+    fn solid_chunk(block: impl Fn(LocalPos) -> Block) -> ChunkSnapshot {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0, 0));
+        for pos in LocalPos::all() {
+            chunk.set_block(block(pos), pos);
+        }
+        let mut grid = ChunkGrid::new();
+        let names = grid.registry.texture_names();
+        grid.set_atlas(Arc::new(AtlasLayout::new(16, names)));
+        grid.set_chunk(chunk);
+        grid.snapshot(ChunkPos::new(0, 0, 0)).unwrap()
+    }
+
+    #[test]
+    fn solid_chunk_meshes_only_its_surface() {
+        let snapshot = solid_chunk(|_| Block::new(1));
+        let data = snapshot.generate_chunk_data();
+        let data = data.layer(RenderClass::Opaque);
+        // every block on each of the six sides shows one face
+        let exposed = 32 * 32 * 6;
+        assert_eq!(exposed * 4, data.vertex_count());
+        assert_eq!(exposed * 4, data.normals.len());
+        assert_eq!(exposed * 6, data.indices.len());
+        assert!(data
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < data.vertex_count()));
+        // no degenerate faces left in the index buffer
+        assert!(data.indices.chunks(3).all(|t| t[0] != t[1] && t[1] != t[2]));
+    }
+
+    #[test]
+    fn ambient_occlusion_across_chunks() {
+        let mut grid = ChunkGrid::new();
+        grid.set_block(WorldPos::new(31, 31, 5), Block::new(1));
+        // diagonally above the +x,-z corner of its top face, in another chunk
+        grid.set_block(WorldPos::new(32, 32, 4), Block::new(1));
+        let snapshot = grid.snapshot(ChunkPos::new(0, 0, 0)).unwrap();
+        let data = snapshot.generate_chunk_data();
+        let data = data.layer(RenderClass::Opaque);
+
+        let top = (0..data.vertex_count())
+            .find(|i| data.normals[*i] == [0., 1., 0.])
+            .unwrap();
+        let light: Vec<f32> = (top..top + 4).map(|i| data.colors[i][0]).collect();
+        assert_eq!(data.positions[top], [31.5, 31.5, 4.5]);
+        assert_eq!(vec![AO_CURVE[2], 1., 1., 1.], light);
+
+        // the darkened corner is kept off the diagonal
+        let top = top as u32;
+        let first = top as usize / 4 * 6;
+        assert_eq!(
+            [top + 1, top + 2, top + 3, top + 3, top, top + 1],
+            data.indices[first..first + 6]
+        );
+    }
+
+    #[test]
+    fn grass_tops_are_tinted() {
+        let mut grid = ChunkGrid::new();
+        let grass = grid.registry.by_name("grass").unwrap();
+        grid.set_block(WorldPos::new(3, 3, 3), grass);
+        let data = grid.generate_chunk_data(ChunkPos::new(0, 0, 0), Mesher::Naive);
+        let data = data.layer(RenderClass::Opaque);
+
+        for i in 0..data.vertex_count() {
+            let [x, _, z] = data.positions[i];
+            let color = &data.colors[i][..3];
+            if data.normals[i] == [0., 1., 0.] {
+                let tint = grid.climate.color(Tint::Grass, x as f64, z as f64);
+                assert_eq!(tint, color);
+                assert_ne!([1.; 3], color);
+            } else {
+                assert_eq!([1.; 3], color);
+            }
+        }
+    }
+
+    #[test]
+    fn render_classes() {
+        let mut grid = ChunkGrid::new();
+        let block = |name| grid.registry.by_name(name).unwrap();
+        let (stone, glass, cobweb, leaves) = (
+            block("stone"),
+            block("glass"),
+            block("cobweb"),
+            block("leaves"),
+        );
+        // a stone with a cobweb on top, two panes of glass and two leaves next to each other
+        grid.set_block(WorldPos::new(1, 1, 1), stone);
+        grid.set_block(WorldPos::new(1, 2, 1), cobweb);
+        grid.set_block(WorldPos::new(5, 1, 1), glass);
+        grid.set_block(WorldPos::new(6, 1, 1), glass);
+        grid.set_block(WorldPos::new(9, 1, 1), leaves);
+        grid.set_block(WorldPos::new(10, 1, 1), leaves);
+        let data = grid.generate_chunk_data(ChunkPos::new(0, 0, 0), Mesher::Naive);
+
+        // the cobweb doesn't hide the stone below it
+        assert_eq!(6 * 4, data.layer(RenderClass::Opaque).vertex_count());
+        // glass hides the faces between panes
+        assert_eq!(10 * 4, data.layer(RenderClass::Translucent).vertex_count());
+        // leaves keep them, the cobweb is two double-sided planes
+        assert_eq!((4 + 12) * 4, data.layer(RenderClass::Cutout).vertex_count());
+        assert_eq!(0, data.layer(RenderClass::Liquid).vertex_count());
+
+        let snapshot = grid.snapshot(ChunkPos::new(0, 0, 0)).unwrap();
+        let faces = snapshot.get_faces(LocalPos::new(5, 1, 1));
+        assert!(!faces[Face::PosX.index()] && faces[Face::NegX.index()]);
+    }
+
+    #[test]
+    fn greedy_merges_solid_chunk() {
+        let snapshot = solid_chunk(|_| Block::new(1));
+        let data = snapshot.generate_greedy_chunk_data();
+        let data = data.layer(RenderClass::Opaque);
+        // one quad per side
+        assert_eq!(6 * 4, data.vertex_count());
+        assert_eq!(6 * 6, data.indices.len());
+        // texture repeats once per block
+        assert!(data.uvs.iter().all(|uv| uv[0] == 0. || uv[0] == 32.));
+        assert!(data.uvs.iter().all(|uv| uv[1] == 0. || uv[1] == 32.));
+        let stone = snapshot.registry.get(Block::new(1)).tile(Face::PosY);
+        assert_ne!(Tile::MISSING, stone);
+        assert!(data.tiles.iter().all(|t| *t == stone.0 as u32));
+    }
+
+    #[test]
+    fn greedy_keeps_textures_apart() {
+        // bottom half stone, top half dirt
+        let snapshot = solid_chunk(|pos| {
+            if pos.y() < 16 {
+                Block::new(1)
+            } else {
+                Block::new(2)
+            }
+        });
+        let data = snapshot.generate_greedy_chunk_data();
+        let data = data.layer(RenderClass::Opaque);
+        // sides along y split in two, top and bottom stay whole
+        assert_eq!((4 * 2 + 2) * 4, data.vertex_count());
+        assert!(data
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < data.vertex_count()));
+    }
+
+    #[test]
+    fn shaped_blocks() {
+        let mut grid = ChunkGrid::new();
+        let (stone, slab, rose) = (
+            grid.registry.by_name("stone").unwrap(),
+            grid.registry.by_name("slab").unwrap(),
+            grid.registry.by_name("rose").unwrap(),
+        );
+        // a slab between two stones, and a rose on its own
+        grid.set_block(WorldPos::new(1, 1, 1), stone);
+        grid.set_block(WorldPos::new(1, 2, 1), slab);
+        grid.set_block(WorldPos::new(1, 3, 1), stone);
+        grid.set_block(WorldPos::new(5, 1, 1), rose);
+
+        for mesher in [Mesher::Naive, Mesher::Greedy] {
+            let data = grid.generate_chunk_data(ChunkPos::new(0, 0, 0), mesher);
+            // the slab hides the top of the stone below, but not the bottom of the one
+            // above, and its own top is never culled
+            let opaque = data.layer(RenderClass::Opaque);
+            assert_eq!((5 + 5 + 6) * 4, opaque.vertex_count());
+            assert!(opaque
+                .normals
+                .iter()
+                .zip(&opaque.positions)
+                .any(|(normal, position)| *normal == [0., 1., 0.] && position[1] == 2.));
+            assert_eq!(4 * 4, data.layer(RenderClass::Cutout).vertex_count());
+        }
+    }
+
+    #[test]
+    fn logs_follow_their_axis() {
+        let mut grid = ChunkGrid::new();
+        let log = grid.registry.by_name("log").unwrap();
+        let names = grid.registry.texture_names();
+        grid.set_atlas(Arc::new(AtlasLayout::new(16, names)));
+        let rings = grid.registry.get(log).tile(Face::PosY).0 as u32;
+        let state = BlockState::default().with_axis(crate::state::Axis::X);
+        grid.set_block(WorldPos::new(3, 3, 3), log.with_state(state));
+        let data = grid.generate_chunk_data(ChunkPos::new(0, 0, 0), Mesher::Naive);
+        let data = data.layer(RenderClass::Opaque);
+
+        for i in 0..data.vertex_count() {
+            let ends = data.normals[i][0] != 0.;
+            assert_eq!(ends, data.tiles[i] == rings);
+        }
+        // the bark on top runs along x, so v changes with x
+        let top: Vec<usize> = (0..data.vertex_count())
+            .filter(|i| data.normals[*i] == [0., 1., 0.])
+            .collect();
+        for pair in top.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let moves_along_x = data.positions[a][0] != data.positions[b][0];
+            assert_eq!(moves_along_x, data.uvs[a][1] != data.uvs[b][1]);
+        }
+    }
+}
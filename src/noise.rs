@@ -0,0 +1,161 @@
+use std::f64::consts::FRAC_1_SQRT_2;
+
+/// Seeded gradient noise, the same for the same seed on every machine.
+/// Used for anything that has to vary smoothly across the world, like climate and terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Noise {
+    seed: u32,
+}
+
+impl Noise {
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+    /// 2D gradient noise in about `-1..1`, 0 at every integer coordinate.
+    pub fn get2(&self, x: f64, z: f64) -> f64 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (fx, fz) = (x - x0, z - z0);
+        let (ix, iz) = (x0 as i32, z0 as i32);
+
+        let corner = |dx: i32, dz: i32| {
+            let [gx, gz] = GRADIENTS_2D[(self.hash(ix + dx, 0, iz + dz) % 8) as usize];
+            gx * (fx - dx as f64) + gz * (fz - dz as f64)
+        };
+        let (u, v) = (fade(fx), fade(fz));
+        lerp(
+            lerp(corner(0, 0), corner(1, 0), u),
+            lerp(corner(0, 1), corner(1, 1), u),
+            v,
+        )
+    }
+    /// 3D gradient noise in about `-1..1`, 0 at every integer coordinate.
+    pub fn get3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let [gx, gy, gz] = GRADIENTS_3D[(self.hash(ix + dx, iy + dy, iz + dz) % 12) as usize];
+            gx * (fx - dx as f64) + gy * (fy - dy as f64) + gz * (fz - dz as f64)
+        };
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        lerp(
+            lerp(
+                lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+                lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+                v,
+            ),
+            lerp(
+                lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+                lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+                v,
+            ),
+            w,
+        )
+    }
+    /// Sums `octaves` layers of [`Noise::get2`], each at twice the frequency and half the
+    /// amplitude of the one before, scaled back to about `-1..1`.
+    pub fn fbm2(&self, x: f64, z: f64, octaves: u32) -> f64 {
+        let (mut sum, mut amplitude, mut frequency, mut total) = (0., 1., 1., 0.);
+        for octave in 0..octaves {
+            // every octave gets its own pattern, otherwise they line up at the origin
+            let layer = Noise::new(self.seed.wrapping_add(octave));
+            sum += layer.get2(x * frequency, z * frequency) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        sum / total
+    }
+    /// Sums `octaves` layers of [`Noise::get3`], see [`Noise::fbm2`].
+    pub fn fbm3(&self, x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+        let (mut sum, mut amplitude, mut frequency, mut total) = (0., 1., 1., 0.);
+        for octave in 0..octaves {
+            let layer = Noise::new(self.seed.wrapping_add(octave));
+            sum += layer.get3(x * frequency, y * frequency, z * frequency) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        sum / total
+    }
+    fn hash(&self, x: i32, y: i32, z: i32) -> u32 {
+        let mut h = self.seed
+            ^ (x as u32).wrapping_mul(0x8da6_b343)
+            ^ (y as u32).wrapping_mul(0xd816_3841)
+            ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb_352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846c_a68b);
+        h ^ (h >> 16)
+    }
+}
+
+const GRADIENTS_2D: [[f64; 2]; 8] = [
+    [1., 0.],
+    [-1., 0.],
+    [0., 1.],
+    [0., -1.],
+    [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    [-FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    [FRAC_1_SQRT_2, -FRAC_1_SQRT_2],
+    [-FRAC_1_SQRT_2, -FRAC_1_SQRT_2],
+];
+
+const GRADIENTS_3D: [[f64; 3]; 12] = [
+    [1., 1., 0.],
+    [-1., 1., 0.],
+    [1., -1., 0.],
+    [-1., -1., 0.],
+    [1., 0., 1.],
+    [-1., 0., 1.],
+    [1., 0., -1.],
+    [-1., 0., -1.],
+    [0., 1., 1.],
+    [0., -1., 1.],
+    [0., 1., -1.],
+    [0., -1., -1.],
+];
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_seeded() {
+        let a = Noise::new(1);
+        let b = Noise::new(2);
+        let samples = |n: Noise| -> Vec<f64> {
+            (0..64)
+                .map(|i| n.fbm2(i as f64 * 0.37, i as f64 * -0.21, 4))
+                .collect()
+        };
+        assert_eq!(samples(a), samples(Noise::new(1)));
+        assert_ne!(samples(a), samples(b));
+        assert_eq!(0., a.get2(3., -7.));
+        assert_eq!(0., a.get3(3., -7., 12.));
+    }
+
+    #[test]
+    fn noise_range() {
+        let n = Noise::new(42);
+        for i in 0..4096 {
+            let (x, y, z) = (i as f64 * 0.173, i as f64 * 0.031, i as f64 * -0.117);
+            assert!((-1.0..=1.0).contains(&n.get2(x, z)));
+            assert!((-1.5..=1.5).contains(&n.get3(x, y, z)));
+            assert!((-1.0..=1.0).contains(&n.fbm2(x, z, 5)));
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::mem::size_of;
+
+use crate::block::Block;
+use crate::coords::CHUNK_VOLUME;
+
+/// The blocks of a chunk as a palette of the distinct blocks in it, plus an index into the
+/// palette per block packed into as few bits as the palette needs. A chunk of a single
+/// block, like all air or all stone, stores no indices at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalettedBlocks {
+    palette: Vec<Option<Block>>,
+    /// Bits per index, 0 while the palette has a single entry.
+    bits: u32,
+    /// Indices, as many per word as fit whole.
+    words: Vec<u64>,
+}
+
+impl PalettedBlocks {
+    /// A chunk filled with `block`.
+    pub fn filled(block: Option<Block>) -> Self {
+        Self {
+            palette: vec![block],
+            bits: 0,
+            words: vec![],
+        }
+    }
+    pub fn get(&self, index: usize) -> Option<Block> {
+        self.palette[self.palette_index(index)]
+    }
+    /// Puts `block` at `index` and returns the block that was there.
+    pub fn set(&mut self, index: usize, block: Option<Block>) -> Option<Block> {
+        let old = self.palette_index(index);
+        if self.palette[old] == block {
+            return block;
+        }
+        let new = match self.palette.iter().position(|b| *b == block) {
+            Some(new) => new,
+            None => {
+                self.palette.push(block);
+                if self.palette.len() > 1 << self.bits {
+                    self.resize(self.bits + 1);
+                }
+                self.palette.len() - 1
+            }
+        };
+        self.write(index, new);
+        self.palette[old]
+    }
+    /// Every block in index order.
+    pub fn iter(&self) -> impl Iterator<Item = Option<Block>> + '_ {
+        (0..CHUNK_VOLUME).map(|i| self.get(i))
+    }
+    /// The block the whole chunk is filled with, if it's only one.
+    pub fn single(&self) -> Option<Option<Block>> {
+        match self.bits {
+            0 => Some(self.palette[0]),
+            _ => None,
+        }
+    }
+    /// Number of distinct blocks, including ones that were since overwritten.
+    pub fn palette_len(&self) -> usize {
+        self.palette.len()
+    }
+    /// Bytes the blocks take up on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.palette.capacity() * size_of::<Option<Block>>()
+            + self.words.capacity() * size_of::<u64>()
+    }
+    /// Drops the palette entries no block uses anymore, falling back to a single block
+    /// when there's only one left.
+    pub fn compact(&mut self) {
+        if self.bits == 0 {
+            return;
+        }
+        let mut used = vec![false; self.palette.len()];
+        for i in 0..CHUNK_VOLUME {
+            used[self.palette_index(i)] = true;
+        }
+        if used.iter().all(|u| *u) {
+            return;
+        }
+        let mut compacted = Self::filled(self.get(0));
+        for i in 0..CHUNK_VOLUME {
+            compacted.set(i, self.get(i));
+        }
+        compacted.palette.shrink_to_fit();
+        *self = compacted;
+    }
+
+    fn per_word(bits: u32) -> usize {
+        (64 / bits) as usize
+    }
+    fn palette_index(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = Self::per_word(self.bits);
+        let shift = (index % per_word) as u32 * self.bits;
+        ((self.words[index / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+    fn write(&mut self, index: usize, value: usize) {
+        let per_word = Self::per_word(self.bits);
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = ((1 << self.bits) - 1) << shift;
+        let word = &mut self.words[index / per_word];
+        *word = (*word & !mask) | ((value as u64) << shift);
+    }
+    /// Repacks the indices with `bits` each.
+    fn resize(&mut self, bits: u32) {
+        let indices: Vec<usize> = (0..CHUNK_VOLUME).map(|i| self.palette_index(i)).collect();
+        self.bits = bits;
+        let per_word = Self::per_word(bits);
+        self.words = vec![0; CHUNK_VOLUME.div_ceil(per_word)];
+        for (i, value) in indices.into_iter().enumerate() {
+            self.write(i, value);
+        }
+    }
+}
+
+impl Default for PalettedBlocks {
+    fn default() -> Self {
+        Self::filled(Block::EMPTY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_grows_with_blocks() {
+        let mut blocks = PalettedBlocks::default();
+        assert_eq!(Some(None), blocks.single());
+        assert_eq!(0, blocks.words.len());
+
+        assert_eq!(None, blocks.set(5, Some(Block::new(1))));
+        assert_eq!(None, blocks.single());
+        assert_eq!(1, blocks.bits);
+        assert_eq!(Some(Block::new(1)), blocks.get(5));
+        assert_eq!(None, blocks.get(4));
+
+        // 17 kinds of blocks need 5 bits, which leave 4 bits of each word unused
+        for id in 2..=16 {
+            blocks.set(id as usize * 1000, Some(Block::new(id)));
+        }
+        assert_eq!(5, blocks.bits);
+        assert_eq!(CHUNK_VOLUME / 12 + 1, blocks.words.len());
+        for id in 2..=16 {
+            assert_eq!(Some(Block::new(id)), blocks.get(id as usize * 1000));
+        }
+        assert_eq!(Some(Block::new(1)), blocks.get(5));
+        assert_eq!(Some(Block::new(16)), blocks.set(16000, Some(Block::new(2))));
+        assert_eq!(
+            CHUNK_VOLUME - 16,
+            blocks.iter().filter(|b| b.is_none()).count()
+        );
+    }
+
+    #[test]
+    fn compacting_drops_unused_blocks() {
+        let mut blocks = PalettedBlocks::filled(Some(Block::new(1)));
+        blocks.set(0, Some(Block::new(2)));
+        blocks.set(1, Some(Block::new(3)));
+        blocks.set(1, Some(Block::new(1)));
+        blocks.compact();
+        assert_eq!(2, blocks.palette_len());
+        assert_eq!(Some(Block::new(2)), blocks.get(0));
+        assert_eq!(Some(Block::new(1)), blocks.get(1));
+
+        blocks.set(0, Some(Block::new(1)));
+        blocks.compact();
+        assert_eq!(Some(Some(Block::new(1))), blocks.single());
+        assert_eq!(0, blocks.words.capacity());
+    }
+}
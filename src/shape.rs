@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::coords::Face;
+use crate::state::{Axis, BlockState};
+
+/// Which half of the block a slab or stair fills, see [`BlockState::half`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+/// The geometry of a block. Box shapes are turned to the block's facing and flipped upside
+/// down when its state says it's in the top half.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub enum Shape {
+    /// A full block, the only shape the greedy mesher merges.
+    #[default]
+    Cube,
+    Slab,
+    /// A slab with a second half slab on top of the side it faces.
+    Stair,
+    /// Two planes crossing diagonally, for cobwebs and plants.
+    Cross,
+    /// Boxes read from a JSON model, see [`BoxModel`].
+    Model(ModelRef),
+}
+
+impl Shape {
+    /// The boxes the block is made of in `state`, in block units from `0` to `1` on every
+    /// axis. Empty for shapes that aren't made of boxes.
+    pub fn boxes(&self, state: BlockState) -> Vec<[Vec3; 2]> {
+        let boxes = match self {
+            Shape::Cube => return vec![[Vec3::ZERO, Vec3::ONE]],
+            Shape::Slab => vec![[Vec3::ZERO, Vec3::new(1., 0.5, 1.)]],
+            // the tall side towards -z, the default facing
+            Shape::Stair => vec![
+                [Vec3::ZERO, Vec3::new(1., 0.5, 1.)],
+                [Vec3::new(0., 0.5, 0.), Vec3::new(1., 1., 0.5)],
+            ],
+            Shape::Cross => vec![],
+            Shape::Model(model) => model.boxes.clone(),
+        };
+        boxes.into_iter().map(|b| orient(b, state)).collect()
+    }
+    /// Whether the shape fully covers `face`, so a neighbour's face touching it is hidden.
+    /// Several boxes can cover a face together, like the back of a stair.
+    pub fn covers(&self, face: Face, state: BlockState) -> bool {
+        let axis = Axis::of(face).index();
+        let positive = face.offset()[axis] > 0;
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let rects: Vec<[Vec3; 2]> = self
+            .boxes(state)
+            .into_iter()
+            .filter(|[from, to]| {
+                if positive {
+                    to[axis] >= 1.
+                } else {
+                    from[axis] <= 0.
+                }
+            })
+            .collect();
+        // split the face along every box edge and check each piece is inside some box
+        let edges = |axis: usize| {
+            let mut edges = vec![0., 1.];
+            for [from, to] in &rects {
+                edges.extend([from[axis].clamp(0., 1.), to[axis].clamp(0., 1.)]);
+            }
+            edges.sort_by(|x: &f32, y| x.partial_cmp(y).unwrap());
+            edges.dedup();
+            edges
+        };
+        let (edges_a, edges_b) = (edges(a), edges(b));
+        edges_a.windows(2).all(|ea| {
+            edges_b.windows(2).all(|eb| {
+                let (ca, cb) = ((ea[0] + ea[1]) / 2., (eb[0] + eb[1]) / 2.);
+                rects.iter().any(|[from, to]| {
+                    (from[a]..=to[a]).contains(&ca) && (from[b]..=to[b]).contains(&cb)
+                })
+            })
+        })
+    }
+}
+
+/// Turns a box a quarter around the block's vertical centre line per facing step, then
+/// flips it for the top half.
+fn orient([from, to]: [Vec3; 2], state: BlockState) -> [Vec3; 2] {
+    let (mut from, mut to) = (from, to);
+    for _ in 0..state.quarter_turns() {
+        // -z turns to +x
+        let turn = |p: Vec3| Vec3::new(1. - p.z, p.y, p.x);
+        let (a, b) = (turn(from), turn(to));
+        from = a.min(b);
+        to = a.max(b);
+    }
+    if state.half() == Half::Top {
+        let (bottom, top) = (1. - to.y, 1. - from.y);
+        from.y = bottom;
+        to.y = top;
+    }
+    [from, to]
+}
+
+/// A model file referenced from the block registry. Its boxes are filled in once the file
+/// is read, until then the block has no geometry.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "String")]
+pub struct ModelRef {
+    pub path: String,
+    pub boxes: Vec<[Vec3; 2]>,
+}
+
+impl From<String> for ModelRef {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            boxes: vec![],
+        }
+    }
+}
+
+/// A block model made of boxes, in sixteenths of a block like the atlas pixels:
+/// `{ "boxes": [{ "from": [0, 0, 0], "to": [16, 8, 16] }] }` is a bottom slab.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BoxModel {
+    pub boxes: Vec<ModelBox>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ModelBox {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+}
+
+impl BoxModel {
+    pub fn from_json(source: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(source)
+    }
+    /// The boxes in block units, see [`Shape::boxes`].
+    pub fn boxes(&self) -> Vec<[Vec3; 2]> {
+        self.boxes
+            .iter()
+            .map(|b| [Vec3::from(b.from) / 16., Vec3::from(b.to) / 16.])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covered_faces() {
+        let covered = |shape: &Shape, state| Face::ALL.map(|f| shape.covers(f, state)) as [bool; 6];
+        let state = BlockState::default();
+        assert_eq!([true; 6], covered(&Shape::Cube, state));
+        assert_eq!([false; 6], covered(&Shape::Cross, state));
+
+        let bottom = covered(&Shape::Slab, state);
+        assert!(bottom[Face::NegY.index()]);
+        assert_eq!(1, bottom.iter().filter(|c| **c).count());
+        let top = covered(&Shape::Slab, state.with_half(Half::Top));
+        assert!(top[Face::PosY.index()] && !top[Face::NegY.index()]);
+
+        // the back and the bottom of the stairs are full
+        let stair = covered(&Shape::Stair, state);
+        assert!(stair[Face::NegY.index()] && stair[Face::NegZ.index()]);
+        assert_eq!(2, stair.iter().filter(|c| **c).count());
+        // turned around and upside down
+        let stair = covered(
+            &Shape::Stair,
+            state.with_facing(Face::PosX).with_half(Half::Top),
+        );
+        assert!(stair[Face::PosY.index()] && stair[Face::PosX.index()]);
+        assert_eq!(2, stair.iter().filter(|c| **c).count());
+    }
+
+    #[test]
+    fn json_models() {
+        let model =
+            BoxModel::from_json(br#"{ "boxes": [{ "from": [0, 0, 0], "to": [16, 8, 16] }] }"#)
+                .unwrap();
+        let shape = Shape::Model(ModelRef {
+            path: "slab.json".to_string(),
+            boxes: model.boxes(),
+        });
+        let state = BlockState::default().with_facing(Face::PosZ);
+        assert_eq!(Shape::Slab.boxes(state), shape.boxes(state));
+        assert!(BoxModel::from_json(b"{ \"boxes\": [{ \"from\": [0, 0] }] }").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::math::Vec3;
+use serde::Deserialize;
+
+use crate::coords::Face;
+use crate::shape::Half;
+
+/// One of the three axes, for blocks like logs that can lie in any direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// The axis a face points along.
+    pub fn of(face: Face) -> Axis {
+        match face {
+            Face::PosX | Face::NegX => Axis::X,
+            Face::PosY | Face::NegY => Axis::Y,
+            Face::PosZ | Face::NegZ => Axis::Z,
+        }
+    }
+    /// Component index of the axis in a vector.
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// How a block's [`BlockState`] is picked when the player places it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Placement {
+    /// Always placed in the default state.
+    #[default]
+    Fixed,
+    /// Lies along the axis of the clicked face, like logs.
+    Axis,
+    /// Faces away from the player, upside down when placed against a ceiling, like stairs.
+    Facing,
+    /// Fills the top half when placed against a ceiling, like slabs.
+    Half,
+}
+
+/// Per-block metadata packed into a byte next to the block id: the axis in bits 0-1, the
+/// horizontal facing in bits 2-3, the half in bit 4, waterlogging in bit 5 and a generic
+/// property from 0 to 3 in bits 6-7. All zero is the state blocks are drawn in unrotated:
+/// along the y axis, facing -z, in the bottom half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockState(u8);
+
+/// Horizontal faces by quarter turn around +y, starting from the default facing.
+const FACINGS: [Face; 4] = [Face::NegZ, Face::PosX, Face::PosZ, Face::NegX];
+const AXES: [Axis; 3] = [Axis::Y, Axis::X, Axis::Z];
+
+impl BlockState {
+    const AXIS: u8 = 0b0000_0011;
+    const FACING: u8 = 0b0000_1100;
+    const HALF: u8 = 0b0001_0000;
+    const WATERLOGGED: u8 = 0b0010_0000;
+    const PROPERTY: u8 = 0b1100_0000;
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+    fn with(self, mask: u8, value: u8) -> Self {
+        Self(self.0 & !mask | (value << mask.trailing_zeros()) & mask)
+    }
+    fn field(self, mask: u8) -> u8 {
+        (self.0 & mask) >> mask.trailing_zeros()
+    }
+    pub fn axis(self) -> Axis {
+        // the unused fourth value reads as the default
+        AXES.get(self.field(Self::AXIS) as usize)
+            .copied()
+            .unwrap_or(Axis::Y)
+    }
+    pub fn with_axis(self, axis: Axis) -> Self {
+        let i = AXES.iter().position(|a| *a == axis).unwrap();
+        self.with(Self::AXIS, i as u8)
+    }
+    /// The horizontal face the block looks towards.
+    pub fn facing(self) -> Face {
+        FACINGS[self.field(Self::FACING) as usize]
+    }
+    /// Turns the block towards `face`. Blocks only face sideways, so up and down leave the
+    /// facing as it is.
+    pub fn with_facing(self, face: Face) -> Self {
+        match FACINGS.iter().position(|f| *f == face) {
+            Some(i) => self.with(Self::FACING, i as u8),
+            None => self,
+        }
+    }
+    /// Quarter turns around +y from the default facing.
+    pub fn quarter_turns(self) -> usize {
+        self.field(Self::FACING) as usize
+    }
+    pub fn half(self) -> Half {
+        if self.0 & Self::HALF != 0 {
+            Half::Top
+        } else {
+            Half::Bottom
+        }
+    }
+    pub fn with_half(self, half: Half) -> Self {
+        self.with(Self::HALF, (half == Half::Top) as u8)
+    }
+    /// Whether the block shares its space with water.
+    pub fn waterlogged(self) -> bool {
+        self.0 & Self::WATERLOGGED != 0
+    }
+    pub fn with_waterlogged(self, waterlogged: bool) -> Self {
+        self.with(Self::WATERLOGGED, waterlogged as u8)
+    }
+    /// A value from 0 to 3 whose meaning is up to the block, like the growth of a plant.
+    pub fn property(self) -> u8 {
+        self.field(Self::PROPERTY)
+    }
+    /// Sets the generic property, keeping only its lowest two bits.
+    pub fn with_property(self, property: u8) -> Self {
+        self.with(Self::PROPERTY, property)
+    }
+    /// The state a block gets when placed against `face` of another block by a player
+    /// looking along `look`.
+    pub fn placed(placement: Placement, face: Face, look: Vec3) -> Self {
+        let state = BlockState::default();
+        // against a ceiling the block hangs from it
+        let half = if face == Face::NegY {
+            Half::Top
+        } else {
+            Half::Bottom
+        };
+        match placement {
+            Placement::Fixed => state,
+            Placement::Axis => state.with_axis(Axis::of(face)),
+            Placement::Half => state.with_half(half),
+            Placement::Facing => {
+                let facing = match (look.x.abs() > look.z.abs(), look.x > 0., look.z > 0.) {
+                    (true, true, _) => Face::PosX,
+                    (true, false, _) => Face::NegX,
+                    (false, _, true) => Face::PosZ,
+                    (false, _, false) => Face::NegZ,
+                };
+                state.with_facing(facing).with_half(half)
+            }
+        }
+    }
+    /// The face of the unrotated block that ends up on `face` once the block lies along its
+    /// axis, and whether its texture has to be turned a quarter to follow the axis.
+    pub fn unrotate(self, face: Face) -> (Face, bool) {
+        let axis = self.axis();
+        let local = match (axis, face) {
+            (Axis::X, Face::PosX) | (Axis::Z, Face::PosZ) => Face::PosY,
+            (Axis::X, Face::NegX) | (Axis::Z, Face::NegZ) => Face::NegY,
+            (Axis::X, Face::PosY) => Face::NegX,
+            (Axis::X, Face::NegY) => Face::PosX,
+            (Axis::Z, Face::PosY) => Face::NegZ,
+            (Axis::Z, Face::NegY) => Face::PosZ,
+            _ => face,
+        };
+        // sides are drawn with v running along y, which has to follow the axis instead
+        let v_axis = match face {
+            Face::PosY | Face::NegY => Axis::Z,
+            _ => Axis::Y,
+        };
+        let is_end = Axis::of(face) == axis;
+        (local, !is_end && v_axis != axis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_fields_are_independent() {
+        let state = BlockState::default()
+            .with_axis(Axis::Z)
+            .with_facing(Face::NegX)
+            .with_half(Half::Top)
+            .with_waterlogged(true)
+            .with_property(2);
+        assert_eq!(Axis::Z, state.axis());
+        assert_eq!(Face::NegX, state.facing());
+        assert_eq!(Half::Top, state.half());
+        assert!(state.waterlogged());
+        assert_eq!(2, state.property());
+
+        let state = state.with_facing(Face::PosY).with_property(5);
+        assert_eq!(Face::NegX, state.facing());
+        assert_eq!(1, state.property());
+        assert_eq!(Axis::Z, state.axis());
+        assert_eq!(state, BlockState::from_bits(state.bits()));
+        assert_eq!(Axis::Y, BlockState::default().axis());
+    }
+
+    #[test]
+    fn placement_follows_the_click() {
+        let placed = |placement, face, look| BlockState::placed(placement, face, look);
+        let forward = Vec3::new(0.2, -0.5, -1.);
+        assert_eq!(
+            BlockState::default(),
+            placed(Placement::Fixed, Face::PosX, forward)
+        );
+        assert_eq!(Axis::X, placed(Placement::Axis, Face::NegX, forward).axis());
+        assert_eq!(Axis::Y, placed(Placement::Axis, Face::PosY, forward).axis());
+        assert_eq!(
+            Half::Top,
+            placed(Placement::Half, Face::NegY, forward).half()
+        );
+        assert_eq!(
+            Half::Bottom,
+            placed(Placement::Half, Face::PosX, forward).half()
+        );
+
+        let stairs = placed(Placement::Facing, Face::PosY, Vec3::new(-1., 0., 0.3));
+        assert_eq!(Face::NegX, stairs.facing());
+        assert_eq!(Half::Bottom, stairs.half());
+    }
+
+    #[test]
+    fn logs_turn_their_rings() {
+        let x = BlockState::default().with_axis(Axis::X);
+        assert_eq!((Face::PosY, false), x.unrotate(Face::PosX));
+        assert_eq!((Face::PosZ, true), x.unrotate(Face::PosZ));
+        assert_eq!(Face::NegX, x.unrotate(Face::PosY).0);
+        let y = BlockState::default();
+        assert!(Face::ALL.iter().all(|f| y.unrotate(*f) == (*f, false)));
+    }
+}
@@ -0,0 +1,659 @@
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::{HashMap, HashSet};
+use futures_lite::future;
+
+use crate::atlas::BlockAtlas;
+use crate::block::{BlockRegistry, RenderClass};
+use crate::chunk::{Chunk, ChunkGrid};
+use crate::coords::{ChunkPos, WorldPos};
+use crate::generator::WorldGenerator;
+use crate::material::ChunkMaterials;
+use crate::mesh::{ChunkMeshData, Mesher};
+
+/// Marker for the world the game is played in. Other worlds (an editor preview, a minimap)
+/// use their own marker type so they get their own [`VoxelWorld`] resource.
+pub struct MainWorld;
+
+/// Owns the [`ChunkGrid`] of one voxel world. Systems get read access through `Res` and write
+/// access through `ResMut`, so the scheduler takes care of locking.
+pub struct VoxelWorld<W: 'static = MainWorld> {
+    grid: ChunkGrid,
+    mesh_tasks: HashMap<ChunkPos, Task<ChunkMeshData>>,
+    /// How many chunks may be meshed in the background at once.
+    pub max_mesh_tasks: usize,
+    pub mesher: Mesher,
+    /// Fills the chunks streamed in around the [`ChunkViewer`]s. Without one the world
+    /// only has the chunks it's given.
+    pub generator: Option<Arc<dyn WorldGenerator>>,
+    /// Chunks around a viewer that get loaded, horizontally.
+    pub load_radius: i32,
+    /// Chunks further than this from every viewer, horizontally, get unloaded. Larger than
+    /// the load radius so chunks don't flicker in and out along the edge.
+    pub unload_radius: i32,
+    /// Chunks above and below a viewer that get loaded, and kept.
+    pub vertical_radius: i32,
+    /// How many chunks may be generated in the background at once.
+    pub max_generate_tasks: usize,
+    /// Keeps chunks that were edited when they're unloaded, and brings them back instead of
+    /// generating them again.
+    pub save_edited: bool,
+    saved: HashMap<ChunkPos, Arc<Chunk>>,
+    generate_tasks: HashMap<ChunkPos, Task<Chunk>>,
+    _marker: PhantomData<fn() -> W>,
+}
+
+impl<W: 'static> VoxelWorld<W> {
+    pub fn new(grid: ChunkGrid) -> Self {
+        Self {
+            grid,
+            mesh_tasks: HashMap::default(),
+            max_mesh_tasks: 16,
+            mesher: Mesher::default(),
+            generator: None,
+            load_radius: 6,
+            unload_radius: 8,
+            vertical_radius: 2,
+            max_generate_tasks: 16,
+            save_edited: true,
+            saved: HashMap::default(),
+            generate_tasks: HashMap::default(),
+            _marker: PhantomData,
+        }
+    }
+    pub fn grid(&self) -> &ChunkGrid {
+        &self.grid
+    }
+    pub fn grid_mut(&mut self) -> &mut ChunkGrid {
+        &mut self.grid
+    }
+    /// The edited chunk kept at `pos` after it was unloaded, see [`VoxelWorld::save_edited`].
+    pub fn saved_chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.saved.get(&pos).map(Arc::as_ref)
+    }
+    /// Whether a chunk at `offset` chunks from a viewer is close enough to be loaded.
+    fn in_load_range(&self, offset: IVec3) -> bool {
+        offset.x * offset.x + offset.z * offset.z <= self.load_radius * self.load_radius
+            && offset.y.abs() <= self.vertical_radius
+    }
+    /// Whether a chunk at `offset` chunks from a viewer is close enough to be kept.
+    fn in_unload_range(&self, offset: IVec3) -> bool {
+        offset.x * offset.x + offset.z * offset.z <= self.unload_radius * self.unload_radius
+            && offset.y.abs() <= self.vertical_radius + 1
+    }
+}
+
+impl<W: 'static> Default for VoxelWorld<W> {
+    fn default() -> Self {
+        Self::new(ChunkGrid::default())
+    }
+}
+
+impl<W: 'static> Deref for VoxelWorld<W> {
+    type Target = ChunkGrid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<W: 'static> DerefMut for VoxelWorld<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.grid
+    }
+}
+
+/// Adds a [`VoxelWorld<W>`] and the systems that turn its chunks into meshes.
+/// Add it once per world marker to run several independent worlds in one app,
+/// together with a single [`ChunkMaterialPlugin`](crate::material::ChunkMaterialPlugin),
+/// [`BlockRegistryPlugin`](crate::block::BlockRegistryPlugin) and
+/// [`BlockAtlasPlugin`](crate::atlas::BlockAtlasPlugin).
+pub struct VoxelWorldPlugin<W: 'static = MainWorld>(PhantomData<fn() -> W>);
+
+impl<W: 'static> Default for VoxelWorldPlugin<W> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<W: 'static> Plugin for VoxelWorldPlugin<W> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelWorld<W>>()
+            .add_system(stream_chunks::<W>.label(ChunkSystem::Stream))
+            .add_system(
+                finish_generating::<W>
+                    .label(ChunkSystem::Generate)
+                    .after(ChunkSystem::Stream),
+            )
+            .add_system(
+                queue_chunks::<W>
+                    .label(ChunkSystem::Queue)
+                    .after(ChunkSystem::Generate),
+            )
+            .add_system(spawn_chunks::<W>.after(ChunkSystem::Queue))
+            .add_system(despawn_chunks::<W>)
+            .add_system(update_registry::<W>)
+            .add_system(update_atlas::<W>);
+    }
+}
+
+/// Marks the entity of a chunk. Its meshes are on children, one per [`RenderClass`]
+/// the chunk has blocks of.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkMesh {
+    pub pos: ChunkPos,
+}
+
+/// Marks a child of a [`ChunkMesh`] entity holding the mesh of one render class.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLayer {
+    pub class: RenderClass,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+enum ChunkSystem {
+    Stream,
+    Generate,
+    Queue,
+}
+
+/// Loads the chunks around it and keeps them loaded, usually put on the camera. Every
+/// world with a [`VoxelWorld::generator`] streams around every viewer.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ChunkViewer;
+
+/// Chunk each viewer is in.
+fn viewer_chunks(viewers: &Query<&GlobalTransform, With<ChunkViewer>>) -> Vec<ChunkPos> {
+    viewers
+        .iter()
+        .map(|transform| {
+            let pos = transform.translation.round().as_ivec3();
+            ChunkPos::from(WorldPos::new(pos.x, pos.y, pos.z))
+        })
+        .collect()
+}
+
+/// Squared distance in chunks to the closest viewer, or 0 without viewers.
+fn viewer_distance(viewers: &[ChunkPos], pos: ChunkPos) -> i32 {
+    viewers
+        .iter()
+        .map(|viewer| {
+            let offset = pos - *viewer;
+            offset.dot(offset)
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+/// Unloads the chunks out of range of every viewer and starts generating the missing ones
+/// in range, nearest first, keeping at most [`VoxelWorld::max_generate_tasks`] jobs in
+/// flight.
+fn stream_chunks<W: 'static>(
+    mut world: ResMut<VoxelWorld<W>>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    thread_pool: Res<AsyncComputeTaskPool>,
+) {
+    let world = &mut *world;
+    let generator = match &world.generator {
+        Some(generator) => generator.clone(),
+        None => return,
+    };
+    let viewers = viewer_chunks(&viewers);
+    if viewers.is_empty() {
+        return;
+    }
+
+    let out_of_range = |world: &VoxelWorld<W>, pos: ChunkPos| {
+        !viewers.iter().any(|v| world.in_unload_range(pos - *v))
+    };
+    let unloaded: Vec<ChunkPos> = world
+        .chunks
+        .keys()
+        .copied()
+        .filter(|pos| out_of_range(world, *pos))
+        .collect();
+    for pos in unloaded {
+        if let Some(chunk) = world.remove_chunk(pos) {
+            if world.save_edited && chunk.edited {
+                world.saved.insert(pos, chunk);
+            }
+        }
+        world.mesh_tasks.remove(&pos);
+    }
+    let cancelled: Vec<ChunkPos> = world
+        .generate_tasks
+        .keys()
+        .copied()
+        .filter(|pos| out_of_range(world, *pos))
+        .collect();
+    for pos in cancelled {
+        world.generate_tasks.remove(&pos);
+    }
+
+    let (load, vertical) = (world.load_radius, world.vertical_radius);
+    let missing: HashSet<ChunkPos> = viewers
+        .iter()
+        .flat_map(|viewer| {
+            (-load..=load).flat_map(move |x| {
+                (-vertical..=vertical)
+                    .flat_map(move |y| (-load..=load).map(move |z| *viewer + IVec3::new(x, y, z)))
+            })
+        })
+        .filter(|pos| {
+            viewers.iter().any(|v| world.in_load_range(*pos - *v))
+                && !world.chunks.contains_key(pos)
+                && !world.generate_tasks.contains_key(pos)
+        })
+        .collect();
+    let mut missing: Vec<ChunkPos> = missing.into_iter().collect();
+    missing.sort_by_key(|pos| (viewer_distance(&viewers, *pos), pos.x, pos.y, pos.z));
+
+    let free = world
+        .max_generate_tasks
+        .saturating_sub(world.generate_tasks.len());
+    for pos in missing.into_iter().take(free) {
+        match world.saved.remove(&pos) {
+            Some(chunk) => {
+                let mut chunk = Chunk::clone(&chunk);
+                chunk.spawned = false;
+                world.set_chunk(chunk);
+            }
+            None => {
+                let generator = generator.clone();
+                let task = thread_pool.spawn(async move { generator.generate(pos) });
+                world.generate_tasks.insert(pos, task);
+            }
+        }
+    }
+}
+
+/// Adds the chunks whose generation finished.
+fn finish_generating<W: 'static>(mut world: ResMut<VoxelWorld<W>>) {
+    let mut finished = vec![];
+    world
+        .generate_tasks
+        .retain(|_, task| match future::block_on(future::poll_once(task)) {
+            Some(chunk) => {
+                finished.push(chunk);
+                false
+            }
+            None => true,
+        });
+    for chunk in finished {
+        world.set_chunk(chunk);
+    }
+}
+
+/// Starts meshing jobs for chunks that were never meshed, or whose blocks changed since,
+/// nearest to a viewer first, keeping at most [`VoxelWorld::max_mesh_tasks`] jobs in flight.
+fn queue_chunks<W: 'static>(
+    mut world: ResMut<VoxelWorld<W>>,
+    viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+    thread_pool: Res<AsyncComputeTaskPool>,
+) {
+    let world = &mut *world;
+    let mesher = world.mesher;
+    let free = world.max_mesh_tasks.saturating_sub(world.mesh_tasks.len());
+    let mut queued: Vec<ChunkPos> = world
+        .grid
+        .chunks
+        .iter()
+        .filter(|(pos, c)| (!c.spawned || c.dirty) && !world.mesh_tasks.contains_key(pos))
+        .map(|(pos, _)| *pos)
+        .collect();
+    let viewers = viewer_chunks(&viewers);
+    queued.sort_by_key(|pos| (viewer_distance(&viewers, *pos), pos.x, pos.y, pos.z));
+    queued.truncate(free);
+
+    for pos in queued {
+        debug!("Queueing chunk {:?}", pos);
+        if let Some(c) = world.grid.get_chunk_mut(pos) {
+            c.spawned = true;
+            c.dirty = false;
+        }
+        if let Some(snapshot) = world.grid.snapshot(pos) {
+            let task = thread_pool.spawn(async move { mesher.mesh(&snapshot) });
+            world.mesh_tasks.insert(pos, task);
+        }
+    }
+}
+
+/// Applies finished meshing jobs. A chunk that already has an entity gets the mesh handles
+/// of its layers swapped, everything else gets a new entity. Layers that became empty are
+/// despawned, new ones are added as children.
+fn spawn_chunks<W: 'static>(
+    mut world: ResMut<VoxelWorld<W>>,
+    mut commands: Commands,
+    materials: Res<ChunkMaterials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    children: Query<&Children>,
+    mut layers: Query<(&ChunkLayer, &mut Handle<Mesh>)>,
+) {
+    let world = &mut *world;
+    let mut finished = vec![];
+    world.mesh_tasks.retain(
+        |pos, task| match future::block_on(future::poll_once(task)) {
+            Some(data) => {
+                finished.push((*pos, data));
+                false
+            }
+            None => true,
+        },
+    );
+
+    for (pos, data) in finished {
+        // unloaded while it was being meshed
+        if !world.chunks.contains_key(&pos) {
+            continue;
+        }
+        let entity = match world.chunk_entity(pos) {
+            Some(entity) => entity,
+            None => {
+                let entity = commands
+                    .spawn_bundle((Transform::default(), GlobalTransform::default()))
+                    .insert(ChunkMesh { pos })
+                    .id();
+                world.entities.insert(pos, entity);
+                entity
+            }
+        };
+
+        let mut existing = [None; 4];
+        for &child in children.get(entity).map_or(&[][..], |c| &**c) {
+            if let Ok((layer, _)) = layers.get_mut(child) {
+                existing[layer.class.index()] = Some(child);
+            }
+        }
+
+        for (class, data) in RenderClass::ALL.into_iter().zip(data.layers) {
+            match existing[class.index()] {
+                Some(child) if data.vertex_count() == 0 => {
+                    commands.entity(child).despawn_recursive();
+                }
+                Some(child) => {
+                    if let Ok((_, mut handle)) = layers.get_mut(child) {
+                        *handle = meshes.add(data.into());
+                    }
+                }
+                None if data.vertex_count() == 0 => {}
+                None => {
+                    let child = commands
+                        .spawn_bundle(MaterialMeshBundle {
+                            mesh: meshes.add(data.into()),
+                            material: materials.get(class),
+                            ..Default::default()
+                        })
+                        .insert(ChunkLayer { class })
+                        .id();
+                    commands.entity(entity).push_children(&[child]);
+                }
+            }
+        }
+
+        debug!("Done meshing chunk {:?}", pos);
+    }
+}
+
+/// Despawns the entities of chunks that were removed from the grid.
+fn despawn_chunks<W: 'static>(mut world: ResMut<VoxelWorld<W>>, mut commands: Commands) {
+    for pos in std::mem::take(&mut world.removed_chunks) {
+        // the chunk may have been loaded again in the meantime
+        if world.chunks.contains_key(&pos) {
+            continue;
+        }
+        if let Some(entity) = world.entities.remove(&pos) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Swaps in the block registry whenever its asset is loaded or edited, and remeshes
+/// everything with it.
+fn update_registry<W: 'static>(
+    mut world: ResMut<VoxelWorld<W>>,
+    mut events: EventReader<AssetEvent<BlockRegistry>>,
+    registries: Res<Assets<BlockRegistry>>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = event {
+            if let Some(registry) = registries.get(handle) {
+                info!("Block registry changed, remeshing");
+                world.set_registry(registry.clone());
+            }
+        }
+    }
+}
+
+/// Remeshes everything with the new layout whenever the block atlas is rebuilt.
+fn update_atlas<W: 'static>(mut world: ResMut<VoxelWorld<W>>, atlas: Option<Res<BlockAtlas>>) {
+    if let Some(atlas) = atlas {
+        if atlas.is_changed() && !Arc::ptr_eq(&atlas.layout, &world.atlas) {
+            info!("Block atlas changed, remeshing");
+            world.set_atlas(atlas.layout.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::tasks::TaskPool;
+
+    use crate::block::Block;
+    use crate::coords::LocalPos;
+    use crate::generator::HeightmapGenerator;
+
+    struct PreviewWorld;
+
+    #[test]
+    fn independent_worlds() {
+        let mut app = App::new();
+        app.init_resource::<VoxelWorld>()
+            .init_resource::<VoxelWorld<PreviewWorld>>();
+
+        let pos = ChunkPos::new(0, 0, 0);
+        let mut chunk = Chunk::new(pos);
+        chunk.set_block(Block::new(1), LocalPos::new(1, 2, 3));
+        app.world
+            .get_resource_mut::<VoxelWorld>()
+            .unwrap()
+            .set_chunk(chunk);
+
+        let main = app.world.get_resource::<VoxelWorld>().unwrap();
+        assert!(main.get_chunk_from_coords(pos).is_some());
+        let preview = app
+            .world
+            .get_resource::<VoxelWorld<PreviewWorld>>()
+            .unwrap();
+        assert!(preview.get_chunk_from_coords(pos).is_none());
+    }
+
+    #[test]
+    fn dirty_chunks_are_requeued() {
+        let mut app = App::new();
+        app.init_resource::<VoxelWorld>()
+            .insert_resource(AsyncComputeTaskPool(TaskPool::new()))
+            .add_system(queue_chunks::<MainWorld>);
+        // forget the jobs that were started instead of waiting for them to finish
+        let queued = |app: &mut App| {
+            let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+            let mut queued: Vec<_> = world.mesh_tasks.drain().map(|(pos, _)| pos).collect();
+            queued.sort_by_key(|p| p.x);
+            queued
+        };
+
+        app.world
+            .get_resource_mut::<VoxelWorld>()
+            .unwrap()
+            .set_block(WorldPos::new(1, 2, 3), Block::new(1));
+        app.update();
+        assert_eq!(vec![ChunkPos::new(0, 0, 0)], queued(&mut app));
+
+        // nothing changed, nothing to remesh
+        app.update();
+        assert!(queued(&mut app).is_empty());
+
+        // an edge block also invalidates the neighbouring chunk
+        let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+        world.set_chunk(Chunk::new(ChunkPos::new(-1, 0, 0)));
+        app.update();
+        queued(&mut app);
+        let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+        world.set_block(WorldPos::new(0, 5, 5), Block::new(1));
+        app.update();
+        assert_eq!(
+            vec![ChunkPos::new(-1, 0, 0), ChunkPos::new(0, 0, 0)],
+            queued(&mut app)
+        );
+    }
+
+    #[test]
+    fn mesh_tasks_are_capped() {
+        let mut app = App::new();
+        app.init_resource::<VoxelWorld>()
+            .insert_resource(AsyncComputeTaskPool(TaskPool::new()))
+            .add_system(queue_chunks::<MainWorld>);
+        let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+        world.max_mesh_tasks = 2;
+        for x in 0..5 {
+            world.set_chunk(Chunk::new(ChunkPos::new(x * 2, 0, 0)));
+        }
+
+        let in_flight = |app: &App| {
+            app.world
+                .get_resource::<VoxelWorld>()
+                .unwrap()
+                .mesh_tasks
+                .len()
+        };
+        app.update();
+        assert_eq!(2, in_flight(&app));
+        // still busy, nothing new is started
+        app.update();
+        assert_eq!(2, in_flight(&app));
+
+        app.world
+            .get_resource_mut::<VoxelWorld>()
+            .unwrap()
+            .mesh_tasks
+            .clear();
+        app.update();
+        assert_eq!(2, in_flight(&app));
+        let world = app.world.get_resource::<VoxelWorld>().unwrap();
+        assert_eq!(4, world.chunks.values().filter(|c| c.spawned).count());
+    }
+
+    #[test]
+    fn removed_chunks_are_despawned() {
+        let mut app = App::new();
+        app.init_resource::<VoxelWorld>()
+            .add_system(despawn_chunks::<MainWorld>);
+        let pos = ChunkPos::new(3, 0, -2);
+        let entity = app.world.spawn().insert(ChunkMesh { pos }).id();
+        let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+        world.set_chunk(Chunk::new(pos));
+        world.entities.insert(pos, entity);
+
+        // still loaded, nothing happens
+        app.update();
+        assert!(app.world.get_entity(entity).is_some());
+
+        let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+        assert!(world.remove_chunk(pos).is_some());
+        assert!(world.remove_chunk(pos).is_none());
+        app.update();
+        assert!(app.world.get_entity(entity).is_none());
+        let world = app.world.get_resource::<VoxelWorld>().unwrap();
+        assert_eq!(None, world.chunk_entity(pos));
+    }
+
+    /// Runs the app until nothing is being generated and `done` holds.
+    fn stream_until(app: &mut App, done: impl Fn(&VoxelWorld) -> bool) {
+        for _ in 0..1000 {
+            app.update();
+            let world = app.world.get_resource::<VoxelWorld>().unwrap();
+            if world.generate_tasks.is_empty() && done(world) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("streaming didn't settle");
+    }
+
+    #[test]
+    fn chunks_stream_around_viewers() {
+        let mut world = VoxelWorld::<MainWorld>::default();
+        world.generator = Some(Arc::new(HeightmapGenerator::new(1, &world.registry)));
+        world.load_radius = 2;
+        world.unload_radius = 3;
+        world.vertical_radius = 0;
+        world.max_generate_tasks = 4;
+        let mut app = App::new();
+        app.insert_resource(world)
+            .insert_resource(AsyncComputeTaskPool(TaskPool::new()))
+            .add_system(stream_chunks::<MainWorld>.label(ChunkSystem::Stream))
+            .add_system(finish_generating::<MainWorld>.after(ChunkSystem::Stream));
+        let viewer = app
+            .world
+            .spawn()
+            .insert(ChunkViewer)
+            .insert(GlobalTransform::default())
+            .id();
+        let around = |center: ChunkPos| {
+            let mut positions: Vec<ChunkPos> = (-2..=2)
+                .flat_map(|x| (-2..=2).map(move |z| center + IVec3::new(x, 0, z)))
+                .filter(|pos| (*pos - center).dot(*pos - center) <= 4)
+                .collect();
+            positions.sort_by_key(|p| (p.x, p.z));
+            positions
+        };
+        let loaded = |world: &VoxelWorld| {
+            let mut positions: Vec<ChunkPos> = world.chunks.keys().copied().collect();
+            positions.sort_by_key(|p| (p.x, p.z));
+            positions
+        };
+
+        // the viewer's chunk and its nearest neighbours come first
+        app.update();
+        let world = app.world.get_resource::<VoxelWorld>().unwrap();
+        let started: Vec<ChunkPos> = world
+            .generate_tasks
+            .keys()
+            .chain(world.chunks.keys())
+            .copied()
+            .collect();
+        assert_eq!(4, started.len());
+        assert!(started.contains(&ChunkPos::new(0, 0, 0)));
+        assert!(started
+            .iter()
+            .all(|pos| pos.as_ivec3().dot(pos.as_ivec3()) <= 1));
+
+        let origin = around(ChunkPos::new(0, 0, 0));
+        stream_until(&mut app, |world| loaded(world) == origin);
+
+        // flying away unloads everything, but keeps the edited chunk
+        let mut world = app.world.get_resource_mut::<VoxelWorld>().unwrap();
+        world.set_block(WorldPos::new(1, 1, 1), Block::new(12));
+        let far = ChunkPos::new(10, 0, 0);
+        *app.world.get_mut::<GlobalTransform>(viewer).unwrap() =
+            GlobalTransform::from_translation(Vec3::new(10. * 32. + 5., 5., 5.));
+        stream_until(&mut app, |world| loaded(world) == around(far));
+        let world = app.world.get_resource::<VoxelWorld>().unwrap();
+        assert!(world.saved_chunk(ChunkPos::new(0, 0, 0)).is_some());
+        assert!(world.saved_chunk(ChunkPos::new(1, 0, 0)).is_none());
+
+        // and brings it back instead of generating it again
+        *app.world.get_mut::<GlobalTransform>(viewer).unwrap() = GlobalTransform::default();
+        stream_until(&mut app, |world| loaded(world) == origin);
+        let world = app.world.get_resource::<VoxelWorld>().unwrap();
+        assert_eq!(
+            Some(Block::new(12)),
+            world.get_block(WorldPos::new(1, 1, 1))
+        );
+        assert!(world.saved_chunk(ChunkPos::new(0, 0, 0)).is_none());
+    }
+}